@@ -1,6 +1,13 @@
 #![feature(test)]
-use anyhow::Result;
-use aoc::Solver;
+use core::fmt;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use aoc::{
+    cpu::{InstructionSet, Machine},
+    ocr,
+    Solver,
+};
 
 // -- Runners --
 fn main() -> Result<()> {
@@ -10,6 +17,7 @@ fn main() -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use aoc::cpu::Halt;
 
     #[test]
     fn part1_test1() -> Result<()> {
@@ -20,8 +28,8 @@ mod tests {
         Day::test(Day::part1, "input", 13220)
     }
     #[test]
-    fn part2_test1() -> Result<()> {
-        Day::test(Day::part2, "test-1", r"##..##..##..##..##..##..##..##..##..##..
+    fn render_test1() -> Result<()> {
+        Day::test(render, "test-1", r"##..##..##..##..##..##..##..##..##..##..
 ###...###...###...###...###...###...###.
 ####....####....####....####....####....
 #####.....#####.....#####.....#####.....
@@ -31,13 +39,52 @@ mod tests {
     }
     #[test]
     fn part2_solution() -> Result<()> {
-        Day::test(Day::part2, "input", r"###..#..#..##..#..#.#..#.###..####.#..#.
-#..#.#..#.#..#.#.#..#..#.#..#.#....#.#..
-#..#.#..#.#..#.##...####.###..###..##...
-###..#..#.####.#.#..#..#.#..#.#....#.#..
-#.#..#..#.#..#.#.#..#..#.#..#.#....#.#..
-#..#..##..#..#.#..#.#..#.###..####.#..#.
-".to_owned())
+        Day::test(Day::part2, "input", "RUAKHBEK".to_owned())
+    }
+    #[test]
+    fn ocr_large_font() -> Result<()> {
+        let image = r"#....#..######..#####.
+#....#..#.......#....#
+#....#..#.......#....#
+#....#..#.......#....#
+######..#####...#####.
+#....#..#.......#..#..
+#....#..#.......#...#.
+#....#..#.......#...#.
+#....#..#.......#....#
+#....#..######..#....#
+";
+        assert_eq!(ocr::recognize(image)?, "HER");
+
+        Ok(())
+    }
+    #[test]
+    fn breakpoint_and_trace() -> Result<()> {
+        let mut cpu = Machine::<Instruction>::load("noop\naddx 3\naddx -5\nnoop")?;
+        cpu.add_breakpoint(2).enable_trace();
+
+        assert_eq!(cpu.run(), Halt::Breakpoint(2));
+        assert_eq!(cpu.registers().x, 4);
+        assert_eq!(cpu.cycle(), 4);
+
+        assert_eq!(cpu.run(), Halt::Finished);
+        assert_eq!(cpu.registers().x, -1);
+        assert_eq!(cpu.trace().len(), 4);
+        assert_eq!(cpu.disassemble(), "0000: noop\n0001: addx 3\n0002: addx -5\n0003: noop\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn breakpoint_on_start() -> Result<()> {
+        let mut cpu = Machine::<Instruction>::load("noop\naddx 3")?;
+        cpu.add_breakpoint(0);
+
+        assert_eq!(cpu.run(), Halt::Breakpoint(0));
+        assert_eq!(cpu.cycle(), 1);
+        assert_eq!(cpu.run(), Halt::Finished);
+
+        Ok(())
     }
 
     // Benchmarks
@@ -54,43 +101,84 @@ mod tests {
     }
 }
 
+#[derive(Debug)]
 enum Instruction {
     NoOp,
     AddX(isize),
 }
 
-struct CPU {
-    x: isize
+impl FromStr for Instruction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split(' ').collect::<Vec<_>>().as_slice() {
+            ["noop"] => Ok(Instruction::NoOp),
+            ["addx", value] => Ok(Instruction::AddX(value.parse()?)),
+            _ => Err(anyhow!("Unknown instruction")),
+        }
+    }
 }
 
-impl CPU {
-    fn new() -> Self {
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::NoOp => write!(f, "noop"),
+            Instruction::AddX(value) => write!(f, "addx {value}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Registers {
+    x: isize,
+}
+
+impl Default for Registers {
+    fn default() -> Self {
         Self { x: 1 }
     }
-    // Executes the given instruction
-    // Returns the number of cycles that it took to execute the instruction
-    // Also returns the value of x during the execution (SO NOT THE FINAL VALUE)
-    fn execute(&mut self, instruction: &Instruction) -> (isize, isize) {
-        match instruction {
-            Instruction::NoOp => (1, self.x),
-            Instruction::AddX(value) => {
-                let state = (2, self.x);
-                self.x += value;
-                return state;
-            }
+}
+
+impl InstructionSet for Instruction {
+    type Registers = Registers;
+
+    fn cycles(&self) -> usize {
+        match self {
+            Instruction::NoOp => 1,
+            Instruction::AddX(_) => 2,
         }
     }
+
+    fn execute(&self, _pc: usize, registers: &mut Self::Registers) -> Option<usize> {
+        if let Instruction::AddX(value) = self {
+            registers.x += value;
+        }
+
+        None
+    }
 }
 
-fn parse(input: &str) -> Vec<Instruction> {
-    input.lines()
-        .map(|line| line.split(" ").collect::<Vec<_>>())
-        .map(|line| match line.as_slice() {
-            ["noop"] => Instruction::NoOp,
-            ["addx", value] => Instruction::AddX(value.parse().unwrap()),
-            _ => panic!("Unknown instruction")
-        })
-        .collect::<Vec<_>>()
+// Draw the CRT image
+fn render(input: &str) -> String {
+    let mut output = "".to_owned();
+
+    let mut cpu = Machine::<Instruction>::load(input).unwrap();
+    cpu.on_cycle(|cycle, registers| {
+        let column = (cycle as isize - 1) % 40;
+        if (column - registers.x).abs() <= 1 {
+            output += "#";
+        } else {
+            output += ".";
+        }
+
+        if column == 39 {
+            output += "\n";
+        }
+    });
+    cpu.run();
+    drop(cpu);
+
+    output
 }
 
 // -- Solution --
@@ -103,52 +191,21 @@ impl aoc::Solver for Day {
     }
 
     fn part1(input: &str) -> Self::Output1 {
-        let instructions = parse(input);
-        let mut cpu = CPU::new();
-
-        let mut cycle = 1;
         let mut sum = 0;
-        for instruction in instructions {
-            let (cycle_count, x) = cpu.execute(&instruction);
-
-            for c in 0..cycle_count {
-                let cc = cycle + c;
-                if (cc+ 20) % 40 == 0 {
-                    sum += x * cc;
-                }
-            }
 
-            cycle += cycle_count;
-        }
+        let mut cpu = Machine::<Instruction>::load(input).unwrap();
+        cpu.on_cycle(|cycle, registers| {
+            if (cycle as isize + 20) % 40 == 0 {
+                sum += registers.x * cycle as isize;
+            }
+        });
+        cpu.run();
+        drop(cpu);
 
         sum
     }
 
     fn part2(input: &str) -> Self::Output2 {
-        let instructions = parse(input);
-        let mut cpu = CPU::new();
-
-        let mut cycle = 1;
-        let mut output = "".to_owned();
-        for instruction in instructions {
-            let (cycle_count, x) = cpu.execute(&instruction);
-
-            for c in 0..cycle_count {
-                let ccm = (cycle + c - 1) % 40;
-                let mut sign = ".";
-                if ccm-1 == x || ccm == x || ccm+1 == x {
-                    sign = "#";
-                }
-                output += sign;
-
-                if ccm == 39 {
-                    output += "\n";
-                }
-            }
-
-            cycle += cycle_count;
-        }
-
-        output
+        ocr::recognize(&render(input)).unwrap()
     }
 }
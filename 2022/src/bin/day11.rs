@@ -1,12 +1,22 @@
 #![feature(test)]
-use std::str::FromStr;
+use core::fmt;
+use std::{iter::Peekable, rc::Rc, str::FromStr};
 
-use anyhow::Result;
-use aoc::Solver;
+use anyhow::{anyhow, Context, Result};
+use aoc::{Output, Solver};
 
 // -- Runners --
 fn main() -> Result<()> {
-    Day::solve()
+    Day::solve_with(&[
+        // Print every inspection of the first rounds (20 by default) using the rules of part 1
+        Output {
+            name: "trace",
+            run: |input, rounds| {
+                let rounds = rounds.map_or(Ok(20), |rounds| rounds.parse().with_context(|| format!("Invalid number of rounds '{rounds}'")))?;
+                trace(input, rounds)
+            },
+        },
+    ])
 }
 
 #[cfg(test)]
@@ -30,6 +40,76 @@ mod tests {
         Day::test(Day::part2, "input", 17926061332)
     }
 
+    #[test]
+    fn expressions() {
+        let old = Exact(7);
+        for (expr, expected) in [("old * old", 49), ("old + 3 * old", 28), ("(old + 3) * old", 70), ("2 * (old - 10)", -6), ("5", 5)] {
+            assert_eq!(expr.parse::<Expr>().unwrap().eval(&old), Some(Exact(expected)), "{expr}");
+        }
+        assert!("old +".parse::<Expr>().is_err());
+        assert!("(old * 2".parse::<Expr>().is_err());
+        assert!("old / 2".parse::<Expr>().is_err());
+    }
+    #[test]
+    fn residues_match_exact() {
+        let moduli: Rc<[i64]> = Rc::from([2, 3, 5, 7, 11]);
+        let expr = "(old - 4) * old + 13".parse::<Expr>().unwrap();
+        for value in -50..50 {
+            let exact = expr.eval(&Exact(value)).unwrap();
+            let residues = expr.eval(&Residues::new(value, moduli.clone())).unwrap();
+            assert_eq!(residues, Residues::new(exact.0, moduli.clone()));
+        }
+
+        // Products of remainders this large do not fit in an i64
+        let moduli: Rc<[i64]> = Rc::from([i64::MAX]);
+        let square = "old * old".parse::<Expr>().unwrap().eval(&Residues::new(i64::MAX - 1, moduli.clone())).unwrap();
+        assert_eq!(square, Residues::new(1, moduli));
+    }
+    #[test]
+    fn report_test1() -> Result<()> {
+        Day::test(|input| {
+            let monkeys = parse(input).unwrap();
+            let moduli: Rc<[i64]> = monkeys.iter().map(|monkey| monkey.divisor).collect();
+            let simulation = Simulation::new(monkeys, 1, |item| Residues::new(item, moduli.clone())).unwrap();
+            let simulation = simulate(simulation, 20).unwrap();
+            simulation.report(1) + &simulation.report(20)
+        }, "test-1", "== After round 1 ==
+Monkey 0 inspected items 2 times.
+Monkey 1 inspected items 4 times.
+Monkey 2 inspected items 3 times.
+Monkey 3 inspected items 6 times.
+== After round 20 ==
+Monkey 0 inspected items 99 times.
+Monkey 1 inspected items 97 times.
+Monkey 2 inspected items 8 times.
+Monkey 3 inspected items 103 times.
+".to_owned())
+    }
+    #[test]
+    fn inspection_trace() -> Result<()> {
+        Day::test(|input| {
+            let mut simulation = Simulation::new(parse(input).unwrap(), 3, Exact).unwrap();
+            simulation.enable_trace();
+            simulation.step().unwrap();
+            let trace = simulation.trace.unwrap();
+            trace.iter().map(|inspection| (inspection.monkey, inspection.target)).collect::<Vec<_>>()
+        }, "test-1", vec![(0, 3), (0, 3), (1, 0), (1, 0), (1, 0), (1, 0), (2, 1), (2, 3), (2, 3), (3, 1), (3, 1), (3, 1), (3, 1), (3, 1)])
+    }
+    #[test]
+    fn trace_output() -> Result<()> {
+        Day::test(|input| {
+            let output = trace(input, 1).unwrap();
+            (output.lines().count(), output.lines().next().unwrap().to_owned())
+        }, "test-1", (19, "Round 1: monkey 0 throws to monkey 3".to_owned()))
+    }
+    #[test]
+    fn exact_overflow() -> Result<()> {
+        Day::test(|input| {
+            let simulation = Simulation::new(parse(input).unwrap(), 1, Exact).unwrap();
+            simulate(simulation, 20).is_err()
+        }, "test-1", true)
+    }
+
     // Benchmarks
     extern crate test;
     #[bench]
@@ -44,37 +124,214 @@ mod tests {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
-enum Operation {
-    Add(i64),
-    AddOld,
-    Multiply(i64),
-    MultiplyOld,
+// Arithmetic expression of the worry level, parsed from the right hand side of the operation
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Old,
+    Constant(i64),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn eval<W: Worry>(&self, old: &W) -> Option<W> {
+        match self {
+            Expr::Old => Some(old.clone()),
+            Expr::Constant(value) => Some(old.constant(*value)),
+            Expr::Add(a, b) => a.eval(old)?.add(&b.eval(old)?),
+            Expr::Sub(a, b) => a.eval(old)?.sub(&b.eval(old)?),
+            Expr::Mul(a, b) => a.eval(old)?.mul(&b.eval(old)?),
+        }
+    }
+}
+
+// Recursive descent parser, '*' binds stronger than '+' and '-'
+struct Parser<'a> {
+    tokens: Peekable<std::vec::IntoIter<&'a str>>,
 }
 
-impl FromStr for Operation {
+impl<'a> Parser<'a> {
+    fn new(s: &'a str) -> Self {
+        let mut tokens = Vec::new();
+        let mut start = None;
+        for (idx, c) in s.char_indices() {
+            if c.is_alphanumeric() {
+                start.get_or_insert(idx);
+                continue;
+            }
+
+            if let Some(start) = start.take() {
+                tokens.push(&s[start..idx]);
+            }
+            if !c.is_whitespace() {
+                tokens.push(&s[idx..idx + c.len_utf8()]);
+            }
+        }
+        if let Some(start) = start {
+            tokens.push(&s[start..]);
+        }
+
+        Self { tokens: tokens.into_iter().peekable() }
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        let mut expr = self.term()?;
+        while let Some(&op) = self.tokens.peek() {
+            let build = match op {
+                "+" => Expr::Add,
+                "-" => Expr::Sub,
+                _ => break,
+            };
+            self.tokens.next();
+            expr = build(Box::new(expr), Box::new(self.term()?));
+        }
+
+        Ok(expr)
+    }
+
+    fn term(&mut self) -> Result<Expr> {
+        let mut expr = self.factor()?;
+        while self.tokens.next_if_eq(&"*").is_some() {
+            expr = Expr::Mul(Box::new(expr), Box::new(self.factor()?));
+        }
+
+        Ok(expr)
+    }
+
+    fn factor(&mut self) -> Result<Expr> {
+        match self.tokens.next() {
+            Some("old") => Ok(Expr::Old),
+            Some("(") => {
+                let expr = self.expr()?;
+                match self.tokens.next() {
+                    Some(")") => Ok(expr),
+                    _ => Err(anyhow!("Missing closing parenthesis")),
+                }
+            }
+            Some(token) => Ok(Expr::Constant(token.parse().with_context(|| format!("Unexpected token '{token}'"))?)),
+            None => Err(anyhow!("Unexpected end of expression")),
+        }
+    }
+}
+
+impl FromStr for Expr {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let operation = s.split(" ").collect::<Vec<_>>();
-
-        match operation.as_slice() {
-            ["old", "*", "old"] => Ok(Operation::MultiplyOld),
-            ["old", "*", value] => Ok(Operation::Multiply(value.parse()?)),
-            ["old", "+", "old"] => Ok(Operation::AddOld),
-            ["old", "+", value] => Ok(Operation::Add(value.parse()?)),
-            _ => Err(anyhow::anyhow!("Invalid input")),
+        let mut parser = Parser::new(s);
+        let expr = parser.expr()?;
+        if let Some(token) = parser.tokens.next() {
+            return Err(anyhow!("Unexpected token '{token}'"));
         }
+
+        Ok(expr)
+    }
+}
+
+// Representation of a worry level, all operations return None if the value can not be represented
+trait Worry: Clone + fmt::Debug {
+    // A constant with the same representation as this value
+    fn constant(&self, value: i64) -> Self;
+    fn add(&self, other: &Self) -> Option<Self>;
+    fn sub(&self, other: &Self) -> Option<Self>;
+    fn mul(&self, other: &Self) -> Option<Self>;
+    // Divide the worry level after the inspection
+    fn relieve(&self, factor: i64) -> Option<Self>;
+    // Check divisibility by the divisor of the monkey with the given index
+    fn divisible(&self, monkey: usize, divisor: i64) -> bool;
+}
+
+// Exact worry level, needed when the worry level gets divided
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Exact(i64);
+
+impl Worry for Exact {
+    fn constant(&self, value: i64) -> Self {
+        Exact(value)
+    }
+
+    fn add(&self, other: &Self) -> Option<Self> {
+        self.0.checked_add(other.0).map(Exact)
+    }
+
+    fn sub(&self, other: &Self) -> Option<Self> {
+        self.0.checked_sub(other.0).map(Exact)
+    }
+
+    fn mul(&self, other: &Self) -> Option<Self> {
+        self.0.checked_mul(other.0).map(Exact)
+    }
+
+    fn relieve(&self, factor: i64) -> Option<Self> {
+        Some(Exact(self.0.div_euclid(factor)))
+    }
+
+    fn divisible(&self, _monkey: usize, divisor: i64) -> bool {
+        self.0 % divisor == 0
+    }
+}
+
+// Worry level stored as its remainder for the divisor of every monkey. This never overflows, but
+// division is not possible.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Residues {
+    moduli: Rc<[i64]>,
+    values: Vec<i64>,
+}
+
+impl Residues {
+    fn new(value: i64, moduli: Rc<[i64]>) -> Self {
+        let values = moduli.iter().map(|modulus| value.rem_euclid(*modulus)).collect();
+        Self { moduli, values }
+    }
+
+    // Every value is below its modulus, so working in i128 keeps the result exact for any modulus
+    fn combine(&self, other: &Self, op: impl Fn(i128, i128) -> i128) -> Self {
+        let values = self
+            .values
+            .iter()
+            .zip(&other.values)
+            .zip(self.moduli.iter())
+            .map(|((a, b), modulus)| op(*a as i128, *b as i128).rem_euclid(*modulus as i128) as i64)
+            .collect();
+
+        Self { moduli: self.moduli.clone(), values }
+    }
+}
+
+impl Worry for Residues {
+    fn constant(&self, value: i64) -> Self {
+        Residues::new(value, self.moduli.clone())
+    }
+
+    fn add(&self, other: &Self) -> Option<Self> {
+        Some(self.combine(other, |a, b| a + b))
+    }
+
+    fn sub(&self, other: &Self) -> Option<Self> {
+        Some(self.combine(other, |a, b| a - b))
+    }
+
+    fn mul(&self, other: &Self) -> Option<Self> {
+        Some(self.combine(other, |a, b| a * b))
+    }
+
+    fn relieve(&self, factor: i64) -> Option<Self> {
+        (factor == 1).then(|| self.clone())
+    }
+
+    fn divisible(&self, monkey: usize, _divisor: i64) -> bool {
+        self.values[monkey] == 0
     }
 }
 
 #[derive(Debug)]
 struct Monkey {
     items: Vec<i64>,
-    operation: Operation,
+    operation: Expr,
     divisor: i64,
     next: (usize, usize),
-    inspects: i64,
 }
 
 impl FromStr for Monkey {
@@ -82,77 +339,182 @@ impl FromStr for Monkey {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let lines = s.lines().collect::<Vec<_>>();
-        let items = lines[1].split_once(": ").unwrap().1.split(", ").map(|num| num.parse().unwrap()).collect();
+        if lines.len() < 6 {
+            return Err(anyhow!("Incomplete monkey description"));
+        }
 
-        let operation: Operation = lines[2].split_once("= ").unwrap().1.parse()?;
+        let items = lines[1]
+            .split_once(": ")
+            .context("Missing starting items")?
+            .1
+            .split(", ")
+            .filter(|num| !num.is_empty())
+            .map(|num| num.parse())
+            .collect::<Result<_, _>>()?;
 
-        let divisor = lines[3].rsplit_once(" ").unwrap().1.parse()?;
+        let operation = lines[2].split_once("= ").context("Missing operation")?.1.parse()?;
 
-        let next_true = lines[4].rsplit_once(" ").unwrap().1.parse()?;
-        let next_false = lines[5].rsplit_once(" ").unwrap().1.parse()?;
+        let last = |line: &str| line.rsplit_once(' ').map(|(_, value)| value.to_owned()).context("Invalid line");
+        let divisor = last(lines[3])?.parse()?;
+        let next_true = last(lines[4])?.parse()?;
+        let next_false = last(lines[5])?.parse()?;
 
-        Ok(Self {items, operation, divisor, next: (next_true, next_false), inspects: 0})
+        Ok(Self { items, operation, divisor, next: (next_true, next_false) })
     }
 }
 
-fn step(monkeys: &mut Vec<Monkey>, div: i64, reducer: i64) {
-    for idx in 0..monkeys.len() {
-        let items = std::mem::take(&mut monkeys[idx].items);
-        for item in items {
-            // Calculate the new item value
-            let mut i = match monkeys[idx].operation {
-                Operation::Add(v) => item + v,
-                Operation::AddOld => item + item,
-                Operation::Multiply(v) => item * v,
-                Operation::MultiplyOld => item * item,
-            } / div;
+// A single item being inspected
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Inspection {
+    round: usize,
+    monkey: usize,
+    target: usize,
+}
 
-            i %= reducer;
+struct Simulation<W: Worry> {
+    monkeys: Vec<Monkey>,
+    items: Vec<Vec<W>>,
+    relief: i64,
+    round: usize,
+    // Number of inspections of every monkey, per round
+    rounds: Vec<Vec<u64>>,
+    trace: Option<Vec<Inspection>>,
+}
 
-            // Increase the inspect counter for this monkey
-            monkeys[idx].inspects += 1;
+impl<W: Worry> Simulation<W> {
+    fn new(monkeys: Vec<Monkey>, relief: i64, worry: impl Fn(i64) -> W) -> Result<Self> {
+        if let Some(monkey) = monkeys.iter().find(|monkey| monkey.next.0.max(monkey.next.1) >= monkeys.len()) {
+            return Err(anyhow!("Monkey throws to monkey {} which does not exist", monkey.next.0.max(monkey.next.1)));
+        }
 
-            let next;
-            if i % monkeys[idx].divisor == 0 {
-                next = monkeys[idx].next.0;
-            } else {
-                next = monkeys[idx].next.1;
+        let items = monkeys
+            .iter()
+            .map(|monkey| monkey.items.iter().map(|item| worry(*item)).collect())
+            .collect();
+
+        Ok(Self { monkeys, items, relief, round: 0, rounds: Vec::new(), trace: None })
+    }
+
+    // Record where every inspected item goes
+    fn enable_trace(&mut self) {
+        self.trace.get_or_insert_with(Vec::new);
+    }
+
+    fn step(&mut self) -> Result<()> {
+        self.round += 1;
+        let mut inspects = vec![0; self.monkeys.len()];
+
+        for (idx, monkey) in self.monkeys.iter().enumerate() {
+            for item in std::mem::take(&mut self.items[idx]) {
+                let item = monkey
+                    .operation
+                    .eval(&item)
+                    .and_then(|item| item.relieve(self.relief))
+                    .with_context(|| format!("Worry level of monkey {idx} can not be represented in round {}", self.round))?;
+
+                inspects[idx] += 1;
+
+                let target = if item.divisible(idx, monkey.divisor) { monkey.next.0 } else { monkey.next.1 };
+                if let Some(trace) = self.trace.as_mut() {
+                    trace.push(Inspection { round: self.round, monkey: idx, target });
+                }
+                self.items[target].push(item);
             }
-            monkeys[next].items.push(i);
         }
+
+        self.rounds.push(inspects);
+
+        Ok(())
     }
-}
 
-fn solution(input: &str, rounds: i32, div: i64) -> i64 {
-    let mut monkeys: Vec<Monkey> = input.split("\n\n").map(|line| line.parse().unwrap()).collect();
+    // Total number of inspections per monkey after the given round
+    fn inspections(&self, round: usize) -> Vec<u64> {
+        self.rounds
+            .iter()
+            .take(round)
+            .fold(vec![0; self.monkeys.len()], |mut total, inspects| {
+                total.iter_mut().zip(inspects).for_each(|(total, count)| *total += count);
+                total
+            })
+    }
+
+    fn monkey_business(&self) -> u64 {
+        let mut inspects = self.inspections(self.round);
+        inspects.sort();
+
+        inspects.into_iter().rev().take(2).product()
+    }
+
+    // Overview in the same format as the puzzle description
+    fn report(&self, round: usize) -> String {
+        let counts = self.inspections(round).iter().enumerate().map(|(idx, count)| {
+            format!("Monkey {idx} inspected items {count} times.\n")
+        }).collect::<String>();
 
-    let reducer = monkeys.iter().fold(1, |acc, monkey| acc * monkey.divisor);
+        format!("== After round {round} ==\n{counts}")
+    }
+}
 
+fn parse(input: &str) -> Result<Vec<Monkey>> {
+    input
+        .trim()
+        .split("\n\n")
+        .enumerate()
+        .map(|(idx, monkey)| monkey.parse().with_context(|| format!("Failed to parse monkey {idx}")))
+        .collect()
+}
+
+fn simulate<W: Worry>(mut simulation: Simulation<W>, rounds: usize) -> Result<Simulation<W>> {
     for _ in 0..rounds {
-        step(&mut monkeys, div, reducer);
+        simulation.step()?;
+    }
+
+    Ok(simulation)
+}
+
+// Where every item went in every round, followed by the overview of the last round
+fn trace(input: &str, rounds: usize) -> Result<String> {
+    let mut simulation = Simulation::new(parse(input)?, 3, Exact)?;
+    simulation.enable_trace();
+    let simulation = simulate(simulation, rounds)?;
+
+    let mut output = String::new();
+    for inspection in simulation.trace.iter().flatten() {
+        output += &format!("Round {}: monkey {} throws to monkey {}\n", inspection.round, inspection.monkey, inspection.target);
     }
 
-    let mut inspects = monkeys.iter().map(|monkey| monkey.inspects).collect::<Vec<_>>();
-    inspects.sort();
+    Ok(output + &simulation.report(rounds))
+}
+
+// Exact worry levels if they get divided, otherwise the remainders for every divisor
+fn solution(input: &str, rounds: usize, relief: i64) -> Result<u64> {
+    let monkeys = parse(input)?;
 
-    inspects.into_iter().rev().take(2).reduce(|acc, inspects| acc * inspects).unwrap()
+    if relief == 1 {
+        let moduli: Rc<[i64]> = monkeys.iter().map(|monkey| monkey.divisor).collect();
+        let simulation = Simulation::new(monkeys, relief, |item| Residues::new(item, moduli.clone()))?;
+        Ok(simulate(simulation, rounds)?.monkey_business())
+    } else {
+        let simulation = Simulation::new(monkeys, relief, Exact)?;
+        Ok(simulate(simulation, rounds)?.monkey_business())
+    }
 }
 
 // -- Solution --
 pub struct Day;
 impl aoc::Solver for Day {
-    type Output1 = i64;
-    type Output2 = i64;
+    type Output1 = u64;
+    type Output2 = u64;
 
     fn day() -> u8 {
         11
     }
 
     fn part1(input: &str) -> Self::Output1 {
-        solution(input, 20, 3)
+        solution(input, 20, 3).unwrap()
     }
 
     fn part2(input: &str) -> Self::Output2 {
-        solution(input, 10000, 1)
+        solution(input, 10000, 1).unwrap()
     }
 }
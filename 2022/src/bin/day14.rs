@@ -3,12 +3,10 @@ use core::fmt;
 use std::{str::FromStr, cmp::{min, max}};
 
 use anyhow::Result;
-use aoc::Solver;
-
-#[cfg(feature = "gif")]
-use gif::{Encoder, Repeat, Frame};
-#[cfg(feature = "gif")]
-use std::{borrow::Cow, fs::File};
+use aoc::{
+    visualize::{Color, Render, Visualizer},
+    Solver,
+};
 
 // -- Runners --
 fn main() -> Result<()> {
@@ -238,32 +236,46 @@ impl<'a> Cave {
         }
     }
 
-    #[cfg(feature = "gif")]
-    fn frame(&self) -> Cow<[u8]> {
-        let buffer = self.grid.iter().flat_map(|line| line.iter().map(|block| match block {
-                    Block::Air => 0,
-                    Block::Rock => 1,
-                    Block::Sand => 2,
-                    Block::Source => 3,
-                    Block::Void => 4,
-        }).collect::<Vec<_>>()).collect::<Vec<u8>>();
-
-        Cow::from(buffer)
-    }
-
     fn count_sand(&self) -> usize {
         self.grid.iter().flatten().filter(|&block| *block == Block::Sand).count()
     }
 }
 
-#[cfg(feature = "gif")]
-fn write_frame<T: std::io::Write>(encoder: &mut Encoder<T>, cave: &Cave, delay: u16) {
-    let mut frame = Frame::default();
-    frame.width = cave.size.0 as u16;
-    frame.height = cave.size.1 as u16;
-    frame.buffer = cave.frame();
-    frame.delay = delay;
-    encoder.write_frame(&frame).unwrap();
+impl Render for Cave {
+    fn size(&self) -> (usize, usize) {
+        self.size
+    }
+
+    fn cell(&self, x: usize, y: usize) -> u8 {
+        match self.grid[y][x] {
+            Block::Air => 0,
+            Block::Rock => 1,
+            Block::Sand => 2,
+            Block::Source => 3,
+            Block::Void => 4,
+        }
+    }
+
+    fn palette(&self) -> &[Color] {
+        &[[0xFF, 0xFF, 0xFF], [0x00, 0x00, 0x00], [0xea, 0xc7, 0x99], [0xFF, 0x00, 0x00], [0x00, 0x00, 0xFF]]
+    }
+}
+
+// Keep adding sand until it stops, optionally rendering every step
+fn fill(cave: &mut Cave, mut visualizer: Option<Visualizer>) {
+    if let Some(visualizer) = visualizer.as_mut() {
+        visualizer.frame_with_delay(cave, 100).unwrap();
+    }
+
+    while cave.simulate_sand() {
+        if let Some(visualizer) = visualizer.as_mut() {
+            visualizer.frame(cave).unwrap();
+        }
+    }
+
+    if let Some(visualizer) = visualizer.as_mut() {
+        visualizer.frame_with_delay(cave, 1000).unwrap();
+    }
 }
 
 // -- Solution --
@@ -278,53 +290,14 @@ impl aoc::Solver for Day {
 
     fn part1(input: &str) -> Self::Output1 {
         let mut cave = Cave::from_str(input, false);
-
-        #[cfg(feature = "gif")]
-        let color_map = &[0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0xea, 0xc7, 0x99, 0xFF, 0x00, 0x00, 0x00, 0x00, 0xFF];
-        #[cfg(feature = "gif")]
-        let mut image = File::create("visualize/14/part1.gif").unwrap();
-        #[cfg(feature = "gif")]
-        let mut encoder = Encoder::new(&mut image, cave.size.0 as u16, cave.size.1 as u16, color_map).unwrap();
-        #[cfg(feature = "gif")]
-        encoder.set_repeat(Repeat::Infinite).unwrap();
-
-        #[cfg(feature = "gif")]
-        write_frame(&mut encoder, &mut cave, 100);
-
-
-        while cave.simulate_sand() {
-            #[cfg(feature = "gif")]
-            write_frame(&mut encoder, &mut cave, 1);
-        }
-
-        #[cfg(feature = "gif")]
-        write_frame(&mut encoder, &mut cave, 1000);
+        fill(&mut cave, Visualizer::from_options(Self::day(), 1));
 
         cave.count_sand()
     }
 
     fn part2(input: &str) -> Self::Output2 {
         let mut cave = Cave::from_str(input, true);
-
-        #[cfg(feature = "gif")]
-        let color_map = &[0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0xea, 0xc7, 0x99, 0xFF, 0x00, 0x00, 0x00, 0x00, 0xFF];
-        #[cfg(feature = "gif")]
-        let mut image = File::create("visualize/14/part2.gif").unwrap();
-        #[cfg(feature = "gif")]
-        let mut encoder = Encoder::new(&mut image, cave.size.0 as u16, cave.size.1 as u16, color_map).unwrap();
-        #[cfg(feature = "gif")]
-        encoder.set_repeat(Repeat::Infinite).unwrap();
-
-        #[cfg(feature = "gif")]
-        write_frame(&mut encoder, &mut cave, 100);
-
-        while cave.simulate_sand() {
-            #[cfg(feature = "gif")]
-            write_frame(&mut encoder, &mut cave, 1);
-        }
-
-        #[cfg(feature = "gif")]
-        write_frame(&mut encoder, &mut cave, 1000);
+        fill(&mut cave, Visualizer::from_options(Self::day(), 2));
 
         cave.count_sand()
     }
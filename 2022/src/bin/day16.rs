@@ -1,12 +1,25 @@
 #![feature(test)]
-use std::{collections::HashMap, str::FromStr, cmp::Ordering};
+use core::fmt;
+use std::{collections::HashMap, str::FromStr, cmp::Ordering, rc::Rc};
 
 use anyhow::Result;
-use aoc::Solver;
+use aoc::{
+    search::{Problem, Search},
+    Output, Solver,
+};
 
 // -- Runners --
 fn main() -> Result<()> {
-    Day::solve()
+    Day::solve_with(&[
+        // Print the valves every agent opens for both parts instead of just the pressure
+        Output {
+            name: "routes",
+            run: |input, _| {
+                let volcano = Volcano::from_str(input)?;
+                Ok(format!("{}\n{}", volcano.plan(&[30]), volcano.plan(&[26, 26])))
+            },
+        },
+    ])
 }
 
 #[cfg(test)]
@@ -30,6 +43,53 @@ mod tests {
         Day::test(Day::part2, "input", 2169)
     }
 
+    #[test]
+    fn search_modes() -> Result<()> {
+        Day::test(|input| {
+            let volcano = Volcano::from_str(input).unwrap();
+            let state = State::new(&[20, 20, 20]);
+            [
+                Search::new(&volcano).run(state.clone()).score,
+                Search::new(&volcano).parallel(true).run(state.clone()).score,
+                Search::new(&volcano).beam(1000).run(state).score,
+                volcano.plan_search(&[20, 20, 20]).pressure as i64,
+                volcano.plan_partition(&[20, 20, 20]).pressure as i64,
+            ]
+        }, "test-1", [1308; 5])
+    }
+    #[test]
+    fn routes() -> Result<()> {
+        Day::test(|input| {
+            let volcano = Volcano::from_str(input).unwrap();
+            volcano.plan(&[30]).to_string()
+        }, "test-1", "Agent 1 (30 minutes): AA -> DD -> BB -> JJ -> HH -> EE -> CC, releasing 1651\nTotal: 1651\n".to_owned())
+    }
+    #[test]
+    fn mixed_budgets() -> Result<()> {
+        Day::test(|input| {
+            let volcano = Volcano::from_str(input).unwrap();
+            let plan = volcano.plan_partition(&[26, 10, 5]);
+            let search = volcano.plan_search(&[26, 10, 5]);
+            assert_eq!(search.routes.iter().map(|route| route.released).sum::<i32>(), search.pressure);
+            let search = search.pressure;
+
+            // Every valve is opened by at most one agent
+            let mut valves = plan.routes.iter().flat_map(|route| route.valves.iter()).collect::<Vec<_>>();
+            let count = valves.len();
+            valves.sort();
+            valves.dedup();
+
+            (plan.pressure == search, valves.len() == count, plan.routes.len())
+        }, "test-1", (true, true, 3))
+    }
+    #[test]
+    fn no_agents() -> Result<()> {
+        Day::test(|input| {
+            let volcano = Volcano::from_str(input).unwrap();
+            [volcano.plan_partition(&[]), volcano.plan_search(&[])].map(|plan| plan.to_string())
+        }, "test-1", ["Total: 0\n".to_owned(), "Total: 0\n".to_owned()])
+    }
+
     // Benchmarks
     extern crate test;
     #[bench]
@@ -157,181 +217,342 @@ impl FromStr for Volcano {
     }
 }
 
+// Valves opened by a single agent, in the order they are opened
+#[derive(Debug, Clone)]
+struct Route {
+    time_remaining: i32,
+    valves: Vec<String>,
+    released: i32,
+}
+
+#[derive(Debug, Clone)]
+struct Plan {
+    pressure: i32,
+    routes: Vec<Route>,
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, route) in self.routes.iter().enumerate() {
+            write!(f, "Agent {} ({} minutes): {STARTING_NAME}", idx + 1, route.time_remaining)?;
+            for valve in &route.valves {
+                write!(f, " -> {valve}")?;
+            }
+            writeln!(f, ", releasing {}", route.released)?;
+        }
+
+        writeln!(f, "Total: {}", self.pressure)
+    }
+}
+
+// Best pressure that a single agent can release for every set of opened valves
+// Valve idx is stored in bit idx-1, as AA (idx 0) never needs to be opened
+struct Table {
+    // Best for opening exactly the valves in the mask
+    exact: Vec<u16>,
+    // Best for opening any subset of the valves in the mask, and which subset that is
+    best: Vec<u16>,
+    from: Vec<usize>,
+}
+
 impl Volcano {
-    fn visit(&self, state: impl State) -> i32 {
-        // There is no time remaining anymore, so no extra pressure is released
-        if state.get_time_remaining() < 0 {
-            return 0;
+    fn table(&self, time_remaining: i32) -> Table {
+        let masks = 1 << (self.size - 1);
+        let mut table = Table {
+            exact: vec![0; masks],
+            best: Vec::new(),
+            from: Vec::new(),
+        };
+
+        self.explore(0, time_remaining, 0, 0, &mut table);
+
+        // Spread the best results to every superset, one bit at a time
+        table.best = table.exact.clone();
+        table.from = (0..masks).collect();
+        for bit in 0..self.size - 1 {
+            for mask in 0..masks {
+                if mask & (1 << bit) != 0 && table.best[mask ^ (1 << bit)] > table.best[mask] {
+                    table.best[mask] = table.best[mask ^ (1 << bit)];
+                    table.from[mask] = table.from[mask ^ (1 << bit)];
+                }
+            }
         }
 
-        // We have just moved to this valve and opened it
-        // Calculate how much pressure we have just released
-        let released_here = state.get_time_remaining() * self.valves[state.get_pos()].flowrate;
+        table
+    }
+
+    fn explore(&self, pos: usize, time_remaining: i32, mask: usize, released: u16, table: &mut Table) {
+        table.exact[mask] = table.exact[mask].max(released);
 
-        // Visit the next valve
-        let mut best = 0;
         for idx in 1..self.size {
-            // But only if it has not been opened yet
-            if state.is_open(idx) {
+            let bit = 1 << (idx - 1);
+            if mask & bit != 0 {
                 continue;
             }
 
-            // Calculate how much it costs to move starting from the location of next 'person' to
-            // move
-            let cost = self.dist[state.get_pos_next()][idx] + 1;
-
-            // Go to the next valve, this will give us the best we can do after going to that
-            // valve
-            let released = self.visit(state.next(idx, cost));
+            let time_remaining = time_remaining - self.dist[pos][idx] - 1;
+            if time_remaining <= 0 {
+                continue;
+            }
 
-            // If it is the best option so far, store it
-            best = best.max(released);
+            // plan() makes sure that the total pressure fits
+            let gain = (time_remaining * self.valves[idx].flowrate) as u16;
+            self.explore(idx, time_remaining, mask | bit, released + gain, table);
         }
-
-        return best + released_here;
     }
-}
 
-trait State {
-    fn new(time_remaining: i32) -> Self;
-    fn next(&self, idx: usize, cost: i32) -> Self;
-    fn is_open(&self, idx: usize) -> bool;
-    fn get_pos(&self) -> usize;
-    fn get_pos_next(&self) -> usize;
-    fn get_time_remaining(&self) -> i32;
-}
+    // Find the order in which to open exactly the valves in the mask to release the given pressure
+    fn route(&self, pos: usize, time_remaining: i32, mask: usize, released: i32, route: &mut Vec<usize>) -> bool {
+        if mask == 0 {
+            return released == 0;
+        }
 
-#[derive(Copy, Clone)]
-struct StateSingle {
-    pos_player: usize,
-    time_remaining: i32,
-    // We can have a max of 64 valves if we store it like this
-    opened: i64,
-}
+        for idx in 1..self.size {
+            let bit = 1 << (idx - 1);
+            if mask & bit == 0 {
+                continue;
+            }
 
-impl State for StateSingle {
-    fn new(time_remaining: i32) -> Self {
-        Self {
-            // Player starts in AA (idx: 0)
-            pos_player: 0,
-            time_remaining,
-            // Start with AA marked as opened so we do not visit it again
-            opened: 1,
+            let time_remaining = time_remaining - self.dist[pos][idx] - 1;
+            let gain = time_remaining * self.valves[idx].flowrate;
+            if time_remaining <= 0 || gain > released {
+                continue;
+            }
+
+            route.push(idx);
+            if self.route(idx, time_remaining, mask ^ bit, released - gain, route) {
+                return true;
+            }
+            route.pop();
         }
+
+        false
     }
 
-    fn next(&self, idx: usize, cost: i32) -> Self {
-        Self {
-            pos_player: idx,
-            time_remaining: self.time_remaining - cost,
-            opened: self.opened | 1 << idx
+    // Find the best way for a group of agents with the given time budgets to open the valves
+    fn plan(&self, budgets: &[i32]) -> Plan {
+        // The partitioning needs tables with an entry for every set of valves, and for more than two
+        // agents it goes through every way of splitting them up, which grows as 3^n
+        let most = budgets.iter().max().copied().unwrap_or(0);
+        let total = self.valves.iter().map(|valve| valve.flowrate * most).sum::<i32>();
+        if self.size - 1 <= MAX_PARTITION_VALVES && total <= u16::MAX as i32 {
+            self.plan_partition(budgets)
+        } else {
+            self.plan_search(budgets)
         }
     }
 
-    fn is_open(&self, idx: usize) -> bool {
-        (self.opened >> idx) & 0x01 == 1
-    }
+    // Every agent gets their own set of valves, so we try every way of partitioning the valves
+    fn plan_partition(&self, budgets: &[i32]) -> Plan {
+        if budgets.is_empty() {
+            return Plan { pressure: 0, routes: Vec::new() };
+        }
 
-    fn get_pos(&self) -> usize {
-        self.pos_player
-    }
+        let masks = 1usize << (self.size - 1);
+        let all = masks - 1;
+
+        let mut cache = HashMap::new();
+        let tables = budgets
+            .iter()
+            .map(|&time| cache.entry(time).or_insert_with(|| Rc::new(self.table(time))).clone())
+            .collect::<Vec<_>>();
+
+        // combined[k][mask] is the best the first k+1 agents can do using only the valves in mask
+        // choice[k][mask] is the set of valves given to agent k in that case
+        let mut combined = vec![tables[0].best.iter().map(|&released| released as i32).collect::<Vec<_>>()];
+        let mut choice = vec![(0..masks).collect::<Vec<_>>()];
+        for (k, table) in tables.iter().enumerate().skip(1) {
+            // For the last agent only the full set matters
+            let targets = if k == budgets.len() - 1 { all..masks } else { 0..masks };
+
+            let mut best = vec![0; masks];
+            let mut chosen = vec![0; masks];
+            for mask in targets {
+                // Iterate over every subset of the mask
+                let mut subset = mask;
+                loop {
+                    let released = table.best[subset] as i32 + combined[k - 1][mask ^ subset];
+                    if released > best[mask] {
+                        best[mask] = released;
+                        chosen[mask] = subset;
+                    }
+
+                    if subset == 0 {
+                        break;
+                    }
+                    subset = (subset - 1) & mask;
+                }
+            }
+
+            combined.push(best);
+            choice.push(chosen);
+        }
 
-    fn get_pos_next(&self) -> usize {
-        self.pos_player
+        // Walk back through the choices to find the route of every agent
+        let mut mask = all;
+        let mut routes = Vec::new();
+        for k in (0..budgets.len()).rev() {
+            let subset = choice[k][mask];
+            let exact = tables[k].from[subset];
+            let released = tables[k].exact[exact] as i32;
+
+            let mut route = Vec::new();
+            let found = self.route(0, budgets[k], exact, released, &mut route);
+            assert!(found, "Every entry in the table is reached by some route");
+            routes.push(Route {
+                time_remaining: budgets[k],
+                valves: route.iter().map(|&idx| self.valves[idx].name.clone()).collect(),
+                released,
+            });
+            mask ^= subset;
+        }
+        routes.reverse();
+
+        Plan {
+            pressure: combined[budgets.len() - 1][all],
+            routes,
+        }
     }
 
-    fn get_time_remaining(&self) -> i32 {
-        self.time_remaining
+    // Search through every order in which the agents can open the valves
+    fn plan_search(&self, budgets: &[i32]) -> Plan {
+        if budgets.is_empty() {
+            return Plan { pressure: 0, routes: Vec::new() };
+        }
+
+        let solution = Search::new(self).parallel(true).run(State::new(budgets));
+
+        let mut routes = budgets
+            .iter()
+            .map(|&time_remaining| Route { time_remaining, valves: Vec::new(), released: 0 })
+            .collect::<Vec<_>>();
+
+        // Every step either moves one agent to a valve, or makes it stop
+        for step in solution.path.windows(2) {
+            let (before, after) = (&step[0], &step[1]);
+            for (route, (a, b)) in routes.iter_mut().zip(before.agents.iter().zip(after.agents.iter())) {
+                if a.pos != b.pos {
+                    route.valves.push(self.valves[b.pos].name.clone());
+                    route.released += after.released - before.released;
+                }
+            }
+        }
+
+        Plan {
+            pressure: solution.score as i32,
+            routes,
+        }
     }
 }
 
-#[derive(Copy, Clone)]
-struct StateDouble {
-    pos_player: usize,
-    pos_elephant: usize,
-    time_remaining_player: i32,
-    time_remaining_elephant: i32,
-    player_turn: bool,
+const MAX_PARTITION_VALVES: usize = 16;
+
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+struct Agent {
+    pos: usize,
+    time_remaining: i32,
+}
+
+#[derive(Debug, Clone)]
+struct State {
+    agents: Vec<Agent>,
     // We can have a max of 64 valves if we store it like this
-    opened: i64,
+    opened: u64,
+    // Total pressure that the opened valves will release before the time runs out
+    released: i32,
 }
 
-impl State for StateDouble {
-    fn new(time_remaining: i32) -> Self {
+impl State {
+    fn new(budgets: &[i32]) -> Self {
         Self {
-            // Player starts in AA (idx: 0)
-            pos_player: 0,
-            pos_elephant: 0,
-            time_remaining_player: time_remaining,
-            time_remaining_elephant: time_remaining,
-            player_turn: true,
+            // Everyone starts in AA (idx: 0)
+            agents: budgets.iter().map(|&time_remaining| Agent { pos: 0, time_remaining }).collect(),
             // Start with AA marked as opened so we do not visit it again
             opened: 1,
+            released: 0,
         }
     }
 
-    fn next(&self, idx: usize, cost: i32) -> Self {
-        let player_turn = !self.player_turn;
-
-        let time_remaining_player = if player_turn {
-            self.time_remaining_player - cost
-        } else {
-            self.time_remaining_player
-        };
+    fn is_open(&self, idx: usize) -> bool {
+        (self.opened >> idx) & 0x01 == 1
+    }
+}
 
-        let time_remaining_elephant = if !player_turn {
-            self.time_remaining_elephant - cost
-        } else {
-            self.time_remaining_elephant
-        };
+impl Problem for Volcano {
+    type State = State;
+    type Key = (u64, Vec<Agent>);
 
-        let pos_player = if player_turn {
-            idx
-        } else {
-            self.pos_player
-        };
-        let pos_elephant = if !player_turn {
-            idx
-        } else {
-            self.pos_elephant
-        };
-
-        Self {
-            pos_player,
-            pos_elephant,
-            time_remaining_player,
-            time_remaining_elephant,
-            player_turn: !self.player_turn,
-            opened: self.opened | (1 << idx),
-        }
+    fn score(&self, state: &State) -> i64 {
+        state.released as i64
     }
 
-    fn get_pos(&self) -> usize {
-        if self.player_turn {
-            self.pos_player
-        } else {
-            self.pos_elephant
+    fn moves(&self, state: &State) -> Vec<State> {
+        // The agent with the most time remaining always moves next
+        let (agent, current) = state
+            .agents
+            .iter()
+            .enumerate()
+            .max_by_key(|(idx, agent)| (agent.time_remaining, std::cmp::Reverse(*idx)))
+            .unwrap();
+
+        if current.time_remaining <= 0 {
+            return Vec::new();
         }
-    }
 
-    fn get_pos_next(&self) -> usize {
-        if self.player_turn {
-            self.pos_elephant
-        } else {
-            self.pos_player
+        let mut moves = (1..self.size)
+            .filter(|&idx| !state.is_open(idx))
+            .filter_map(|idx| {
+                // Move to the valve and open it
+                let time_remaining = current.time_remaining - self.dist[current.pos][idx] - 1;
+                if time_remaining <= 0 {
+                    return None;
+                }
+
+                let mut next = state.clone();
+                next.agents[agent] = Agent { pos: idx, time_remaining };
+                next.opened |= 1 << idx;
+                next.released += time_remaining * self.valves[idx].flowrate;
+                Some(next)
+            })
+            .collect::<Vec<_>>();
+
+        // This agent can also stop and leave the remaining valves to the others, this is also
+        // the only option if it can not reach any valve in time anymore
+        let others = state.agents.iter().enumerate().any(|(idx, other)| idx != agent && other.time_remaining > 0);
+        if moves.is_empty() || others {
+            let mut next = state.clone();
+            next.agents[agent].time_remaining = 0;
+            moves.push(next);
         }
+
+        moves
     }
 
-    fn get_time_remaining(&self) -> i32 {
-        if self.player_turn {
-            self.time_remaining_player
-        } else {
-            self.time_remaining_elephant
-        }
+    fn bound(&self, state: &State) -> i64 {
+        // Assume every closed valve is opened by whoever can get there first, without having to
+        // walk between them
+        let remaining = (1..self.size)
+            .filter(|&idx| !state.is_open(idx))
+            .map(|idx| {
+                let time = state
+                    .agents
+                    .iter()
+                    .map(|agent| agent.time_remaining - self.dist[agent.pos][idx] - 1)
+                    .max()
+                    .unwrap_or(0)
+                    .max(0);
+
+                time * self.valves[idx].flowrate
+            })
+            .sum::<i32>();
+
+        (state.released + remaining) as i64
     }
 
-    fn is_open(&self, idx: usize) -> bool {
-        (self.opened >> idx) & 0x01 == 1
+    fn key(&self, state: &State) -> Option<Self::Key> {
+        let mut agents = state.agents.clone();
+        agents.sort();
+        Some((state.opened, agents))
     }
 }
 
@@ -347,11 +568,11 @@ impl aoc::Solver for Day {
 
     fn part1(input: &str) -> Self::Output1 {
         let volcano = Volcano::from_str(input).unwrap();
-        volcano.visit(StateSingle::new(30))
+        volcano.plan(&[30]).pressure
     }
 
     fn part2(input: &str) -> Self::Output2 {
         let volcano = Volcano::from_str(input).unwrap();
-        volcano.visit(StateDouble::new(26))
+        volcano.plan(&[26, 26]).pressure
     }
 }
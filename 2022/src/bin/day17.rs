@@ -1,9 +1,11 @@
 #![feature(test)]
-use anyhow::Result;
-use aoc::Solver;
+use std::collections::{HashMap, VecDeque};
 
-// @TODO Can be made faster using bitset and shifting for moving left and right
-// Should also make collision detection much faster
+use anyhow::{anyhow, bail, Result};
+use aoc::{
+    visualize::{Color, Render, Visualizer},
+    Solver,
+};
 
 // -- Runners --
 fn main() -> Result<()> {
@@ -31,6 +33,35 @@ mod tests {
         Day::test(Day::part2, "input", 1566272189352)
     }
 
+    #[test]
+    fn pattern_detector() -> Result<()> {
+        Day::test(|input| {
+            [2022, 5000, 7919].map(|rocks| {
+                let mut field = Field::standard(input);
+                for _ in 0..rocks {
+                    field.drop_rock();
+                }
+
+                (field.height(), Field::standard(input).height_after(rocks))
+            })
+        }, "test-1", [(3068, 3068), (7577, 7577), (11997, 11997)])
+    }
+    #[test]
+    fn custom_rules() -> Result<()> {
+        let shapes = Shape::parse_set("###\n\n#.\n##\n\n#\n#")?;
+        assert!(Shape::parse_set(&"#".repeat(65)).is_err());
+        let jets = parse_jets("<<>><>>><")?;
+        assert!(Field::new(4, shapes.clone(), jets.clone()).is_err());
+
+        let mut field = Field::new(5, shapes.clone(), jets.clone())?;
+        for _ in 0..3000 {
+            field.drop_rock();
+        }
+        assert_eq!(Field::new(5, shapes, jets)?.height_after(3000), field.height());
+
+        Ok(())
+    }
+
     // Benchmarks
     extern crate test;
     #[bench]
@@ -45,238 +76,281 @@ mod tests {
     }
 }
 
-#[derive(Debug)]
-struct Shape {
-    shape: u32,
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum Jet {
+    Left,
+    Right,
 }
 
-const WIDTH: usize = 7;
-const SPACE_ABOVE: usize = 3;
+impl TryFrom<char> for Jet {
+    type Error = anyhow::Error;
+
+    fn try_from(c: char) -> Result<Self, Self::Error> {
+        match c {
+            '<' => Ok(Jet::Left),
+            '>' => Ok(Jet::Right),
+            _ => Err(anyhow!("Unexpected direction '{c}'")),
+        }
+    }
+}
+
+fn parse_jets(input: &str) -> Result<Vec<Jet>> {
+    input.trim().chars().map(Jet::try_from).collect()
+}
+
+// The rocks from the puzzle, in the order they fall
+const ROCKS: &str = "####
+
+.#.
+###
+.#.
+
+..#
+..#
+###
+
+#
+#
+#
+#
+
+##
+##";
+
+// A rock is stored as one bitmask per row, starting with the bottom row
+// Bit 0 is the left most column of the rock
+#[derive(Debug, Clone)]
+struct Shape {
+    rows: Vec<u64>,
+    width: usize,
+}
 
 impl Shape {
-    fn new(s: &[&str]) -> Self {
-        let shape = s.into_iter()
+    fn new(s: &[&str]) -> Result<Self> {
+        let width = s
+            .iter()
+            .map(|line| line.rfind('#').map_or(0, |x| x + 1))
+            .max()
+            .unwrap_or(0);
+        // Rows are stored as u64 bitmasks, so wider rocks would overflow them
+        if width > 64 {
+            bail!("Shape can be at most 64 wide, got {width}");
+        }
+
+        let rows = s
+            .iter()
             .rev()
             .map(|line| {
                 line.chars()
                     .enumerate()
-                    .fold(0, |acc, (x, c)| {
-                        if c == '#' {
-                            acc | 1 << x
-                        } else {
-                            acc
-                        }
-                    })
-            }).enumerate()
-            .fold(0, |acc, (y, x)| {
-                acc | x << 8*y
-            });
-
-        Self {
-            shape
-        }
+                    .filter(|&(_, c)| c == '#')
+                    .fold(0, |acc, (x, _)| acc | 1 << x)
+            })
+            .collect();
+
+        Ok(Self { rows, width })
     }
 
-    fn shape(&self) -> u32 {
-        return self.shape;
+    // Parse a set of rocks drawn as ASCII art, separated by empty lines
+    fn parse_set(s: &str) -> Result<Vec<Shape>> {
+        s.split("\n\n")
+            .map(|block| Shape::new(&block.lines().collect::<Vec<_>>()))
+            .collect()
     }
 
     fn get_shapes() -> Vec<Shape> {
-        vec![
-            // Horizontal
-            Shape::new(&[
-                "..####.",
-            ]),
-            // Plus
-            Shape::new(&[
-                "...#...",
-                "..###..",
-                "...#...",
-            ]),
-            // Corner
-            Shape::new(&[
-                "....#..",
-                "....#..",
-                "..###..",
-            ]),
-            // Vertical
-            Shape::new(&[
-                "..#....",
-                "..#....",
-                "..#....",
-                "..#....",
-            ]),
-            // Square
-            Shape::new(&[
-                "..##...",
-                "..##...",
-            ]),
-        ]
+        Self::parse_set(ROCKS).unwrap()
     }
 }
 
+// Distance between the left wall and a new rock
+const SPAWN_X: usize = 2;
+// Distance between the highest rock and a new rock
+const SPACE_ABOVE: usize = 3;
+
 struct Field {
-    map: Vec<u8>,
+    width: usize,
+    shapes: Vec<Shape>,
+    jets: Vec<Jet>,
+    // Every row is stored as a bitmask, bit 0 is the left most column
+    map: Vec<u64>,
     heights: Vec<usize>,
+    // Index of the next shape and jet
+    shape: usize,
+    jet: usize,
+    rocks: usize,
 }
 
 impl Field {
-    fn new() -> Self {
-        Self {
-            map: Vec::new(),
-            heights: vec![0; WIDTH],
+    fn new(width: usize, shapes: Vec<Shape>, jets: Vec<Jet>) -> Result<Self> {
+        if width > 64 {
+            bail!("Field can be at most 64 wide");
         }
-    }
-
-    fn get_mask(&self, y: usize) -> u32 {
-        // Create a mask of all the occupied spaces
-        u32::from_le_bytes([self.map[y], self.map[y+1], self.map[y+2], self.map[y+3]])
-    }
-
-    fn move_left(&self, y: usize, shape: u32) -> u32 {
-        // Create a mask for the left most bits
-        let mask = 0b00000001_00000001_00000001_00000001;
-
-        // If any part of the shape is in the left most bit we can not move to the left
-        if shape & mask != 0 {
-            return shape;
+        if shapes.is_empty() || jets.is_empty() {
+            bail!("Need at least one shape and one jet");
         }
-
-        // Move the shape to the left
-        let moved = shape >> 1;
-
-        let mask = self.get_mask(y);
-
-        // If there is overlap we can not move
-        if moved & mask != 0 {
-            return shape;
+        if let Some(shape) = shapes.iter().find(|shape| SPAWN_X + shape.width > width) {
+            bail!("Shape {shape:?} does not fit in a field that is {width} wide");
         }
 
-        // No collision detected
-        return moved;
+        Ok(Self {
+            width,
+            shapes,
+            jets,
+            map: Vec::new(),
+            heights: vec![0; width],
+            shape: 0,
+            jet: 0,
+            rocks: 0,
+        })
     }
 
-    fn move_right(&self, y: usize, shape: u32) -> u32 {
-        // Move the shape to the right
-        let moved = shape << 1;
-
-        // Create the mask for the left wall and occupied spaces
-        let mask = 0b10000000_10000000_10000000_10000000 |
-            self.get_mask(y);
-
-        // If there is overlap we can not move
-        if moved & mask != 0 {
-            return shape;
-        }
-
-        // No collision detected
-        return moved;
+    // The field from the puzzle
+    fn standard(input: &str) -> Self {
+        Self::new(7, Shape::get_shapes(), parse_jets(input).unwrap()).unwrap()
     }
 
-    fn collision_down(&self, y: usize, shape: u32) -> bool {
-        // Hit the floor
-        if y == 0 {
+    fn collides(&self, shape: &Shape, x: usize, y: usize) -> bool {
+        if x + shape.width > self.width {
             return true;
         }
 
-        // Create a mask of all occuiped spaces one level down
-        let mask = self.get_mask(y-1);
-
-        if shape & mask != 0 {
-            return true;
-        }
-
-        return false;
+        shape
+            .rows
+            .iter()
+            .enumerate()
+            .any(|(i, row)| self.map.get(y + i).is_some_and(|line| line & (row << x) != 0))
     }
 
-    fn land(&mut self, y: usize, shape: u32) {
-        for i in 0..4 {
-            self.map[y+i] |= ((shape >> 8*i) & 0xFF) as u8;
-            for x in 0..WIDTH {
-                if (self.map[y+i] >> x) & 1 == 1 {
-                    self.heights[x] = self.heights[x].max(y+i+1);
+    fn land(&mut self, shape: usize, x: usize, y: usize) {
+        for (i, row) in self.shapes[shape].rows.iter().enumerate() {
+            if self.map.len() <= y + i {
+                self.map.resize(y + i + 1, 0);
+            }
+            self.map[y + i] |= row << x;
+
+            for (column, height) in self.heights.iter_mut().enumerate() {
+                if (row << x >> column) & 1 == 1 {
+                    *height = (*height).max(y + i + 1);
                 }
             }
         }
     }
 
-    fn height(&self) -> usize {
-        *self.heights.iter().max().unwrap()
+    fn drop_rock(&mut self) {
+        let index = self.shape;
+        let shape = &self.shapes[index];
+        let (mut x, mut y) = (SPAWN_X, self.height() + SPACE_ABOVE);
+
+        loop {
+            let jet = self.jets[self.jet];
+            self.jet = (self.jet + 1) % self.jets.len();
+
+            let moved = match jet {
+                Jet::Left => x.checked_sub(1),
+                Jet::Right => Some(x + 1),
+            };
+            if let Some(moved) = moved.filter(|&moved| !self.collides(shape, moved, y)) {
+                x = moved;
+            }
+
+            if y == 0 || self.collides(shape, x, y - 1) {
+                break;
+            }
+            y -= 1;
+        }
+
+        self.land(index, x, y);
+        self.shape = (self.shape + 1) % self.shapes.len();
+        self.rocks += 1;
     }
 
-    fn height_min(&self) -> usize {
+    fn height(&self) -> usize {
         *self.heights.iter().max().unwrap()
     }
 
-    fn expand(&mut self) {
-        let max = self.height();
+    // Every empty cell that can be reached from above the tower, row by row going down from the
+    // top, a rock can never end up anywhere else
+    fn reachable(&self) -> Vec<u64> {
+        let height = self.height();
+        let free = |x: usize, y: usize| (self.map[y] >> x) & 1 == 0;
+
+        let mut reachable = Vec::new();
+        let mut queue: VecDeque<_> = (0..self.width).map(|x| (x, height)).collect();
+        while let Some((x, y)) = queue.pop_front() {
+            let neighbors = [
+                x.checked_sub(1).map(|x| (x, y)),
+                (x + 1 < self.width).then_some((x + 1, y)),
+                y.checked_sub(1).map(|y| (x, y)),
+                (y + 1 < height).then_some((x, y + 1)),
+            ];
+
+            for (x, y) in neighbors.into_iter().flatten() {
+                if y == height || !free(x, y) {
+                    continue;
+                }
 
-        while self.map.len() < (max + 4 + SPACE_ABOVE) as usize {
-            self.map.push(0);
+                let depth = height - 1 - y;
+                if reachable.len() <= depth {
+                    reachable.resize(depth + 1, 0);
+                }
+                if reachable[depth] & (1 << x) == 0 {
+                    reachable[depth] |= 1 << x;
+                    queue.push_back((x, y));
+                }
+            }
         }
+
+        reachable
     }
 
-    fn check_for_pattern(&self, initial_height: usize) -> usize {
-        // Check a window starting from the initial height up to the lowest column,
-        // as above that the rows might still change
-        let height = self.height_min()-initial_height;
-        if height % 2 != 0 {
-            return 0;
-        }
+    // Everything that determines how the next rocks will fall: the next shape, the next jet and
+    // the part of the tower that is still reachable from above
+    fn state(&self) -> (usize, usize, Vec<u64>) {
+        (self.shape, self.jet, self.reachable())
+    }
 
-        let bottom = &self.map[initial_height as usize..(height/2 + initial_height) as usize];
-        let top = &self.map[(height/2+initial_height) as usize..(height + initial_height) as usize];
+    // Height of the tower after the given amount of rocks have fallen
+    // Once the state repeats the remaining full cycles are skipped
+    fn height_after(&mut self, rocks: usize) -> usize {
+        let mut seen = HashMap::new();
 
-        assert_eq!(bottom.len(), top.len(), "Height: {height}");
+        while self.rocks < rocks {
+            if let Some((start, height)) = seen.insert(self.state(), (self.rocks, self.height())) {
+                let period = self.rocks - start;
+                let growth = self.height() - height;
+                let cycles = (rocks - self.rocks) / period;
 
-        // Check if the top and bottom half are the same
-        for y in 0..(height/2) as usize {
-            if bottom[y] != top[y] {
-                return 0;
+                for _ in 0..(rocks - self.rocks) % period {
+                    self.drop_rock();
+                }
+
+                return self.height() + cycles * growth;
             }
+
+            self.drop_rock();
         }
 
-        return height/2;
+        self.height()
     }
-
-    // fn print(&self) {
-    //     for line in self.map.iter().rev() {
-    //         for x in 0..WIDTH {
-    //             if (line >> x) & 1 == 1 {
-    //                 print!("#");
-    //             } else {
-    //                 print!(".");
-    //             }
-    //         }
-    //         println!("");
-    //     }
-    // }
 }
 
-fn simulate_next_block<'a, S, O>(shapes: &mut S, operator: &mut O, field: &mut Field) where
-    S: Iterator<Item = &'a Shape>,
-    O: Iterator<Item = char>
-{
-    let mut shape = shapes.next().unwrap().shape();
-
-    field.expand();
+// Only the top of the tower is drawn
+const VISIBLE_ROWS: usize = 40;
 
-    let mut y = field.height() + SPACE_ABOVE;
-    loop {
-        let direction = operator.next().unwrap();
+impl Render for Field {
+    fn size(&self) -> (usize, usize) {
+        (self.width, VISIBLE_ROWS)
+    }
 
-        match direction {
-            '<' => shape = field.move_left(y, shape),
-            '>' => shape = field.move_right(y, shape),
-            _ => panic!("Unexpected direction"),
-        }
+    fn cell(&self, x: usize, y: usize) -> u8 {
+        let top = self.height().max(VISIBLE_ROWS);
+        let row = self.map.get(top - 1 - y).copied().unwrap_or(0);
+        ((row >> x) & 1) as u8
+    }
 
-        if field.collision_down(y, shape) {
-            field.land(y, shape);
-            break;
-        } else {
-            y -= 1;
-        }
+    fn palette(&self) -> &[Color] {
+        &[[0x20, 0x20, 0x20], [0xA0, 0x80, 0x60]]
     }
 }
 
@@ -291,69 +365,21 @@ impl aoc::Solver for Day {
     }
 
     fn part1(input: &str) -> Self::Output1 {
-        let shapes = Shape::get_shapes();
-        let mut shapes = shapes.iter().cycle();
-        let mut operator = input.trim().chars().cycle();
-        let mut field = Field::new();
+        let mut field = Field::standard(input);
+        let mut visualizer = Visualizer::from_options(Self::day(), 1).map(|v| v.scale(8).delay(5));
 
         for _ in 0..2022 {
-            simulate_next_block(&mut shapes, &mut operator, &mut field);
-        }
+            field.drop_rock();
 
+            if let Some(visualizer) = visualizer.as_mut() {
+                visualizer.frame(&field).unwrap();
+            }
+        }
 
         field.height()
     }
 
     fn part2(input: &str) -> Self::Output2 {
-        let mut operator = input.trim().chars().cycle();
-        let shapes = Shape::get_shapes();
-        let mut shapes = shapes.iter().cycle();
-
-        let mut field = Field::new();
-
-        // The start of the tower is not part of the pattern
-        // So we drop a number of blocks first to make sure we get to the periodic part
-        let initial_rock_count = 200;
-        for _ in 0..initial_rock_count {
-            simulate_next_block(&mut shapes, &mut operator, &mut field);
-        }
-
-        let initial_height = field.height();
-
-        // We now start dropping blocks
-        // After every block we look for a pattern by taking the part added since the initial drop
-        // And then splitting for a given height, splitting it in two
-        // If these two are the same we have found a pattern and now it's height
-        let mut total_dropped = initial_rock_count;
-        let mut pattern_height = 0;
-        for i in 0..100_000 {
-            simulate_next_block(&mut shapes, &mut operator, &mut field);
-
-            let height = field.check_for_pattern(initial_height);
-            if  height != 0 {
-                pattern_height = height;
-                total_dropped += i+1;
-                break;
-            }
-        }
-        assert_ne!(pattern_height, 0);
-
-        // @TODO This does not always give the right answer, depending on initial_rock_count we
-        // sometimes get a +1 error, however for my input it works like this
-        let rocks_in_pattern = (total_dropped - initial_rock_count)/2;
-        println!("rocks_in_pattern: {rocks_in_pattern}");
-
-        let total = 1000000000000;
-        // For some reason this calculation is wrong...
-        // let already_dropped = initial_rock_count + 4*rocks_in_pattern + 1;
-        let already_dropped = total_dropped;
-        let repeat_count = (total - already_dropped) / rocks_in_pattern;
-        let remaining = total - already_dropped - repeat_count * rocks_in_pattern;
-
-        for _ in 0..remaining {
-            simulate_next_block(&mut shapes, &mut operator, &mut field);
-        }
-
-        field.height() + repeat_count*pattern_height
+        Field::standard(input).height_after(1000000000000)
     }
 }
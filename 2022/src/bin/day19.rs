@@ -1,12 +1,25 @@
 #![feature(test)]
-use std::{ops::{AddAssign, SubAssign}, str::FromStr};
+use core::fmt;
+use std::{fmt::Write, ops::{AddAssign, SubAssign}, str::FromStr};
 
-use anyhow::Result;
-use aoc::Solver;
+use anyhow::{bail, Result};
+use aoc::{
+    search::{Problem, Search},
+    Output, Solver,
+};
 
 // -- Runners --
 fn main() -> Result<()> {
-    Day::solve()
+    Day::solve_with(&[
+        // Print the best build order for every blueprint instead of the answers
+        Output {
+            name: "report",
+            run: |input, _| {
+                let blueprints = input.trim().lines().flat_map(Blueprint::from_str).collect::<Vec<_>>();
+                report(&blueprints, 24)
+            },
+        },
+    ])
 }
 
 #[cfg(test)]
@@ -30,6 +43,52 @@ mod tests {
         Day::test(Day::part2, "input", 6000)
     }
 
+    #[test]
+    fn replay_best_plan() -> Result<()> {
+        Day::test(|input| {
+            let blueprints = input.trim().lines().flat_map(Blueprint::from_str).collect::<Vec<_>>();
+            blueprints.iter().map(|blueprint| {
+                let timeline = blueprint.replay(&blueprint.best_plan(24)).unwrap();
+                timeline.last().unwrap().geodes
+            }).collect::<Vec<_>>()
+        }, "test-1", vec![9, 12])
+    }
+    #[test]
+    fn replay_example_plan() -> Result<()> {
+        Day::test(|input| {
+            let blueprint = Blueprint::from_str(input.lines().next().unwrap()).unwrap();
+            let mut plan = Plan {
+                minutes: 24,
+                purchases: vec![
+                    (3, Robot::Clay),
+                    (5, Robot::Clay),
+                    (7, Robot::Clay),
+                    (11, Robot::Obsidian),
+                    (12, Robot::Clay),
+                    (15, Robot::Obsidian),
+                    (18, Robot::Geode),
+                    (21, Robot::Geode),
+                ],
+            };
+            let geodes = blueprint.replay(&plan).unwrap().last().unwrap().geodes;
+
+            // Buying the first obsidian robot a minute earlier is not possible
+            plan.purchases[3].0 = 10;
+            (geodes, blueprint.replay(&plan).is_err())
+        }, "test-1", (9, true))
+    }
+    #[test]
+    fn report_test1() -> Result<()> {
+        Day::test(|input| {
+            let blueprints = input.trim().lines().flat_map(Blueprint::from_str).collect::<Vec<_>>();
+            let report = report(&blueprints, 24).unwrap();
+            report.lines().filter(|line| line.starts_with("Blueprint")).map(str::to_owned).collect::<Vec<_>>()
+        }, "test-1", vec![
+            "Blueprint 1: 9 geodes, quality level 9".to_owned(),
+            "Blueprint 2: 12 geodes, quality level 24".to_owned(),
+        ])
+    }
+
     // Benchmarks
     extern crate test;
     #[bench]
@@ -136,149 +195,252 @@ impl FromStr for Blueprint {
     }
 }
 
-impl Blueprint {
-    fn visit(&self, state: State, mut best: isize) -> isize {
-        // If we have run out of time return the amount of geodes we have broken in total
-        if state.time_remaining <= 0 {
-            return state.geodes;
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+struct State {
+    resources: Resources,
+    robots: Resources,
+    // Since we never use the geodes we add all that a robot will crack as soon as it is built
+    geodes: isize,
+    time_remaining: isize,
+}
+
+impl State {
+    fn new(time_remaining: isize) -> Self {
+        Self {
+            resources: Resources::new(0, 0, 0),
+            robots: Resources::new(1, 0, 0),
+            geodes: 0,
+            time_remaining,
         }
+    }
 
-        // Using n(n+1)/2 calculate the maximum possible geodes that we can crack
-        // If we can not improve the best so far, we end here
-        // !!! This make a massive difference, from who knows how long to less then a second
-        if state.geodes + (state.time_remaining-1) * state.time_remaining / 2 < best {
-            return 0;
+    // Wait until we have enough resources to build a robot and then build it
+    // Returns None if it can not be built in time
+    fn build(&self, cost: &Resources) -> Option<Self> {
+        let mut next = self.clone();
+        while !next.resources.enough_for(cost) {
+            next.time_remaining -= 1;
+            next.resources += self.robots;
+
+            if next.time_remaining <= 1 {
+                return None;
+            }
         }
 
+        // Building takes a minute, during which the robots keep collecting
+        next.time_remaining -= 1;
+        next.resources += self.robots;
+        next.resources -= *cost;
+
+        Some(next)
+    }
+}
+
+impl Problem for Blueprint {
+    type State = State;
+    type Key = ();
+
+    fn score(&self, state: &State) -> i64 {
+        state.geodes as i64
+    }
+
+    fn moves(&self, state: &State) -> Vec<State> {
+        let mut moves = Vec::new();
+        let t = state.time_remaining;
+
         // Given the remaining time, calculate how much of the resource we still need in the worst
         // case scenerio. If current stockpile + future production if lower we will attempt to
         // construct a robot, otherwise we do not need them anymore
-        // === Ore ===
-        if state.robots.ore * state.time_remaining + state.resources.ore < self.max.ore * state.time_remaining {
-            // Check if we can construct one right now
-            if state.resources.enough_for(&self.ore_robot_cost) {
-                // Create a new state
-                let mut next = state.next();
-                // Substract the resources
-                next.resources -= self.ore_robot_cost;
-                // Add the robot
+        if state.robots.ore * t + state.resources.ore < self.max.ore * t {
+            if let Some(mut next) = state.build(&self.ore_robot_cost) {
                 next.robots.ore += 1;
-
-                best = best.max(self.visit(next, best));
-            } else {
-                // If we can not construct one right now, skip to the next point in time that we
-                // can build one
-                let mut next = state.next();
-                while !next.resources.enough_for(&self.ore_robot_cost) && next.time_remaining >= 0 {
-                    next = next.next();
-                }
-
-                best = best.max(self.visit(next, best));
+                moves.push(next);
             }
         }
 
-        // === Clay ===
-        if state.robots.clay * state.time_remaining + state.resources.clay < self.max.clay * state.time_remaining {
-            if state.resources.enough_for(&self.clay_robot_cost) {
-                // Create a new state
-                let mut next = state.next();
-                // Substract the resources
-                next.resources -= self.clay_robot_cost;
-                // Add the robot
+        if state.robots.clay * t + state.resources.clay < self.max.clay * t {
+            if let Some(mut next) = state.build(&self.clay_robot_cost) {
                 next.robots.clay += 1;
-
-                best = best.max(self.visit(next, best));
-            } else {
-                // If we can not construct one right now, skip to the next point in time that we
-                // can build one
-                let mut next = state.next();
-                while !next.resources.enough_for(&self.clay_robot_cost) && next.time_remaining >= 0 {
-                    next = next.next();
-                }
-
-                best = best.max(self.visit(next, best));
+                moves.push(next);
             }
         }
 
-        // === Obsidian ===
-        if state.robots.obsidian * state.time_remaining + state.resources.obsidian < self.max.obsidian * state.time_remaining {
-            if state.resources.enough_for(&self.obsidian_robot_cost) {
-                // Create a new state
-                let mut next = state.next();
-                // Substract the resources
-                next.resources -= self.obsidian_robot_cost;
-                // Add the robot
+        if state.robots.obsidian * t + state.resources.obsidian < self.max.obsidian * t {
+            if let Some(mut next) = state.build(&self.obsidian_robot_cost) {
                 next.robots.obsidian += 1;
-
-                best = best.max(self.visit(next, best));
-            } else {
-                // If we can not construct one right now, skip to the next point in time that we
-                // can build one
-                let mut next = state.next();
-                while !next.resources.enough_for(&self.obsidian_robot_cost) && next.time_remaining >= 0 {
-                    next = next.next();
-                }
-
-                best = best.max(self.visit(next, best));
+                moves.push(next);
             }
         }
 
-        // === Geode ==
-        // There is no upper limit to producing robots, so only check if we can make one
-        if state.resources.enough_for(&self.geode_robot_cost) {
-            // Create a new state
-            let mut next = state.next();
-            // Substract the resources
-            next.resources -= self.geode_robot_cost;
-            // Since we never use the geodes we can add all that this robot will mine at once
-            next.geodes += state.time_remaining - 1;
-
-            best = best.max(self.visit(next, best));
-        } else {
-            // If we can not construct one right now, skip to the next point in time that we
-            // can build one
-            let mut next = state.next();
-            while !next.resources.enough_for(&self.geode_robot_cost) && next.time_remaining >= 0 {
-                next = next.next();
-            }
-
-            best = best.max(self.visit(next, best));
+        // There is no upper limit to producing geode robots
+        if let Some(mut next) = state.build(&self.geode_robot_cost) {
+            next.geodes += next.time_remaining;
+            moves.push(next);
         }
 
-        return best;
+        moves
+    }
+
+    fn bound(&self, state: &State) -> i64 {
+        // Using n(n+1)/2 calculate the maximum possible geodes that we can crack, if we build a
+        // geode robot every remaining minute
+        // !!! This make a massive difference, from who knows how long to less then a second
+        let t = state.time_remaining;
+        (state.geodes + (t - 1) * t / 2) as i64
     }
 }
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
-struct State {
+fn max_geodes(blueprint: &Blueprint, time_remaining: isize) -> isize {
+    Search::new(blueprint).run(State::new(time_remaining)).score as isize
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Robot {
+    Ore,
+    Clay,
+    Obsidian,
+    Geode,
+}
+
+impl fmt::Display for Robot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Robot::Ore => write!(f, "ore"),
+            Robot::Clay => write!(f, "clay"),
+            Robot::Obsidian => write!(f, "obsidian"),
+            Robot::Geode => write!(f, "geode"),
+        }
+    }
+}
+
+// Which robot to buy at which minute, minutes start at 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Plan {
+    minutes: isize,
+    purchases: Vec<(isize, Robot)>,
+}
+
+// State at the end of a minute
+#[derive(Debug, Clone)]
+struct Minute {
+    minute: isize,
+    purchase: Option<Robot>,
     resources: Resources,
-    robots: Resources,
     geodes: isize,
-    time_remaining: isize,
+    robots: Resources,
+    geode_robots: isize,
 }
 
-impl State {
-    fn new(time_remaining: isize) -> Self {
-        Self {
-            resources: Resources::new(0, 0, 0),
-            robots: Resources::new(1, 0, 0),
-            geodes: 0,
-            time_remaining,
+impl Blueprint {
+    fn cost(&self, robot: Robot) -> Resources {
+        match robot {
+            Robot::Ore => self.ore_robot_cost,
+            Robot::Clay => self.clay_robot_cost,
+            Robot::Obsidian => self.obsidian_robot_cost,
+            Robot::Geode => self.geode_robot_cost,
         }
     }
 
-    fn next(&self) -> Self {
-        let mut next = self.clone();
+    // Find the build order that cracks the most geodes
+    fn best_plan(&self, minutes: isize) -> Plan {
+        let solution = Search::new(self).run(State::new(minutes));
+
+        let purchases = solution
+            .path
+            .windows(2)
+            .map(|step| {
+                let (before, after) = (&step[0], &step[1]);
+                let robot = if after.robots.ore > before.robots.ore {
+                    Robot::Ore
+                } else if after.robots.clay > before.robots.clay {
+                    Robot::Clay
+                } else if after.robots.obsidian > before.robots.obsidian {
+                    Robot::Obsidian
+                } else {
+                    Robot::Geode
+                };
+
+                // The robot is bought at the start of the last minute that passed
+                (minutes - after.time_remaining, robot)
+            })
+            .collect();
 
-        // Update the time
-        next.time_remaining -= 1;
-        // Collect resources
-        next.resources += self.robots;
+        Plan { minutes, purchases }
+    }
+
+    // Simulate the plan minute by minute, fails if a robot is bought without enough resources
+    fn replay(&self, plan: &Plan) -> Result<Vec<Minute>> {
+        let mut resources = Resources::new(0, 0, 0);
+        let mut robots = Resources::new(1, 0, 0);
+        let (mut geodes, mut geode_robots) = (0, 0);
+
+        let mut purchases = plan.purchases.iter().peekable();
+        let mut timeline = Vec::new();
+        for minute in 1..=plan.minutes {
+            // Spend the resources at the start of the minute
+            let purchase = purchases.next_if(|(at, _)| *at == minute).map(|(_, robot)| *robot);
+            if let Some(robot) = purchase {
+                let cost = self.cost(robot);
+                if !resources.enough_for(&cost) {
+                    bail!("Minute {minute}: Not enough resources for a {robot} robot");
+                }
+                resources -= cost;
+            }
 
-        return next;
+            // Collect with the robots we had at the start of the minute
+            resources += robots;
+            geodes += geode_robots;
+
+            // The new robot is ready at the end of the minute
+            match purchase {
+                Some(Robot::Ore) => robots.ore += 1,
+                Some(Robot::Clay) => robots.clay += 1,
+                Some(Robot::Obsidian) => robots.obsidian += 1,
+                Some(Robot::Geode) => geode_robots += 1,
+                None => {}
+            }
+
+            timeline.push(Minute { minute, purchase, resources, geodes, robots, geode_robots });
+        }
+
+        if let Some((minute, _)) = purchases.next() {
+            bail!("Purchase at minute {minute} is out of order or after the time runs out");
+        }
+
+        Ok(timeline)
     }
 }
 
+// Minute by minute overview of the best plan for every blueprint
+fn report(blueprints: &[Blueprint], minutes: isize) -> Result<String> {
+    let mut output = String::new();
+    for (idx, blueprint) in blueprints.iter().enumerate() {
+        let timeline = blueprint.replay(&blueprint.best_plan(minutes))?;
+        let geodes = timeline.last().map_or(0, |minute| minute.geodes);
+        writeln!(output, "Blueprint {}: {geodes} geodes, quality level {}", idx + 1, geodes * (idx as isize + 1))?;
+
+        for minute in timeline {
+            let action = minute.purchase.map_or(String::new(), |robot| format!("build {robot} robot"));
+            writeln!(
+                output,
+                "{:>3}: {action:<21} ore {:>2}, clay {:>2}, obsidian {:>2}, geodes {:>2} | robots {}/{}/{}/{}",
+                minute.minute,
+                minute.resources.ore,
+                minute.resources.clay,
+                minute.resources.obsidian,
+                minute.geodes,
+                minute.robots.ore,
+                minute.robots.clay,
+                minute.robots.obsidian,
+                minute.geode_robots,
+            )?;
+        }
+        writeln!(output)?;
+    }
+
+    Ok(output)
+}
+
 // -- Solution --
 pub struct Day;
 impl aoc::Solver for Day {
@@ -292,21 +454,21 @@ impl aoc::Solver for Day {
     fn part1(input: &str) -> Self::Output1 {
         let blueprints = input.trim().lines().flat_map(Blueprint::from_str).collect::<Vec<_>>();
 
-        let state = State::new(24);
         blueprints.iter()
             .enumerate()
             .map(|(idx, blueprint)| {
-                blueprint.visit(state.clone(), 0) * (idx as isize + 1)
+                // Replaying the plan also checks that it can actually be built
+                let timeline = blueprint.replay(&blueprint.best_plan(24)).unwrap();
+                timeline.last().map_or(0, |minute| minute.geodes) * (idx as isize + 1)
             }).sum()
     }
 
     fn part2(input: &str) -> Self::Output2 {
         let blueprints = input.trim().lines().flat_map(Blueprint::from_str).collect::<Vec<_>>();
-        let state = State::new(32);
         blueprints.iter()
             .take(3)
             .fold(1, |acc, blueprint| {
-                acc * blueprint.visit(state.clone(), 0)
+                acc * max_geodes(blueprint, 32)
             })
     }
 }
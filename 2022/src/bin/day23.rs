@@ -2,7 +2,10 @@
 use std::{collections::HashMap, str::FromStr};
 
 use anyhow::Result;
-use aoc::Solver;
+use aoc::{
+    visualize::{Color, Render, Visualizer},
+    Solver,
+};
 
 // -- Runners --
 fn main() -> Result<()> {
@@ -203,20 +206,56 @@ impl Map {
         (max.x - min.x) * (max.y - min.y) - self.elves.len() as isize
     }
 
-    // fn print(&self) {
-    //     let (min, max) = self.get_size();
-    //     for y in min.y..max.y {
-    //         for x in min.x..max.x {
-    //             if self.elves.contains_key(&Vec2::new(x, y)) {
-    //                 print!("#");
-    //             } else {
-    //                 print!(".");
-    //             }
-    //         }
-    //         println!("");
-    //     }
-    //     println!("");
-    // }
+}
+
+// Draws a fixed area around the elves, so the view does not jump around when they spread out
+struct View<'a> {
+    map: &'a Map,
+    min: Vec2,
+    max: Vec2,
+}
+
+impl Render for View<'_> {
+    fn size(&self) -> (usize, usize) {
+        ((self.max.x - self.min.x) as usize, (self.max.y - self.min.y) as usize)
+    }
+
+    fn cell(&self, x: usize, y: usize) -> u8 {
+        let pos = Vec2::new(self.min.x + x as isize, self.min.y + y as isize);
+        self.map.elves.contains_key(&pos) as u8
+    }
+
+    fn palette(&self) -> &[Color] {
+        &[[0x10, 0x40, 0x10], [0xFF, 0xFF, 0xFF]]
+    }
+}
+
+// Render every round, the view covers every position the elves reach
+fn animate(input: &str, rounds: Option<usize>, mut visualizer: Visualizer) {
+    let mut map = Map::from_str(input).unwrap();
+    let mut frames = vec![map.elves.clone()];
+    while rounds.is_none_or(|rounds| frames.len() <= rounds) {
+        map.consider();
+        let moved = map.make_moves();
+        frames.push(map.elves.clone());
+
+        if !moved {
+            break;
+        }
+    }
+
+    let (mut min, mut max) = (Vec2::new(isize::MAX, isize::MAX), Vec2::new(isize::MIN, isize::MIN));
+    for elves in &frames {
+        map.elves = elves.clone();
+        let (start, end) = map.get_size();
+        min = Vec2::new(min.x.min(start.x), min.y.min(start.y));
+        max = Vec2::new(max.x.max(end.x), max.y.max(end.y));
+    }
+
+    for elves in frames {
+        map.elves = elves;
+        visualizer.frame(&View { map: &map, min, max }).unwrap();
+    }
 }
 
 // -- Solution --
@@ -230,6 +269,10 @@ impl aoc::Solver for Day {
     }
 
     fn part1(input: &str) -> Self::Output1 {
+        if let Some(visualizer) = Visualizer::from_options(Self::day(), 1) {
+            animate(input, Some(10), visualizer.scale(4).delay(50));
+        }
+
         let mut map = Map::from_str(input).unwrap();
 
         for _ in 0..10 {
@@ -241,6 +284,10 @@ impl aoc::Solver for Day {
     }
 
     fn part2(input: &str) -> Self::Output2 {
+        if let Some(visualizer) = Visualizer::from_options(Self::day(), 2) {
+            animate(input, None, visualizer.scale(4).delay(5));
+        }
+
         let mut map = Map::from_str(input).unwrap();
 
         let mut counter = 0;
@@ -2,7 +2,10 @@
 use std::{collections::{HashMap, VecDeque, HashSet}, str::FromStr, ops::Add};
 
 use anyhow::Result;
-use aoc::Solver;
+use aoc::{
+    visualize::{Color, Render, Visualizer},
+    Solver,
+};
 
 // -- Runners --
 fn main() -> Result<()> {
@@ -198,6 +201,69 @@ impl Map {
     }
 }
 
+// State of the valley at a specific point in time
+struct Valley {
+    size: Vec2,
+    // Number of blizzards in every position
+    blizzards: Vec<usize>,
+}
+
+impl Valley {
+    fn new(map: &Map, time: usize) -> Self {
+        let mut blizzards = vec![0; (map.size.x * map.size.y) as usize];
+        let time = time as isize;
+
+        for (position, direction) in &map.storm {
+            let (x, y) = match direction {
+                Direction::Up => (position.x, (position.y - time).rem_euclid(map.size.y)),
+                Direction::Down => (position.x, (position.y + time).rem_euclid(map.size.y)),
+                Direction::Left => ((position.x - time).rem_euclid(map.size.x), position.y),
+                Direction::Right => ((position.x + time).rem_euclid(map.size.x), position.y),
+            };
+            blizzards[(y * map.size.x + x) as usize] += 1;
+        }
+
+        Self { size: map.size, blizzards }
+    }
+}
+
+impl Render for Valley {
+    // Includes the walls around the valley
+    fn size(&self) -> (usize, usize) {
+        (self.size.x as usize + 2, self.size.y as usize + 2)
+    }
+
+    fn cell(&self, x: usize, y: usize) -> u8 {
+        let (x, y) = (x as isize - 1, y as isize - 1);
+
+        // Entrance and exit
+        if (x, y) == (0, -1) || (x, y) == (self.size.x - 1, self.size.y) {
+            return 1;
+        }
+
+        if x < 0 || y < 0 || x >= self.size.x || y >= self.size.y {
+            return 0;
+        }
+
+        match self.blizzards[(y * self.size.x + x) as usize] {
+            0 => 1,
+            1 => 2,
+            _ => 3,
+        }
+    }
+
+    fn palette(&self) -> &[Color] {
+        // Wall, ground, blizzard and multiple blizzards
+        &[[0x40, 0x40, 0x40], [0xFF, 0xFF, 0xFF], [0x80, 0xC0, 0xFF], [0x20, 0x60, 0xFF]]
+    }
+}
+
+fn animate(map: &Map, minutes: usize, mut visualizer: Visualizer) {
+    for time in 0..=minutes {
+        visualizer.frame(&Valley::new(map, time)).unwrap();
+    }
+}
+
 impl FromStr for Map {
     type Err = anyhow::Error;
 
@@ -249,7 +315,13 @@ impl aoc::Solver for Day {
         let start = Vec2::new(0, -1);
         let end = &map.size + &Vec2::new(-1, -1);
 
-        map.pathfind(start, end, 0)
+        let minutes = map.pathfind(start, end, 0);
+
+        if let Some(visualizer) = Visualizer::from_options(Self::day(), 1) {
+            animate(&map, minutes, visualizer.scale(4).delay(10));
+        }
+
+        minutes
     }
 
     fn part2(input: &str) -> Self::Output2 {
@@ -260,6 +332,12 @@ impl aoc::Solver for Day {
 
         let trip = map.pathfind(start, end, 0);
         let trip = map.pathfind(&end + &Vec2::new(0, 1), &start + &Vec2::new(0, 1), trip);
-        map.pathfind(start, end, trip)
+        let minutes = map.pathfind(start, end, trip);
+
+        if let Some(visualizer) = Visualizer::from_options(Self::day(), 2) {
+            animate(&map, minutes, visualizer.scale(4).delay(10));
+        }
+
+        minutes
     }
 }
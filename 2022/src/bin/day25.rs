@@ -1,5 +1,12 @@
 #![feature(test)]
-use anyhow::Result;
+use core::fmt;
+use std::{
+    iter,
+    ops::{Add, Mul, Neg, Sub},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Result};
 use aoc::Solver;
 
 // -- Runners --
@@ -20,6 +27,68 @@ mod tests {
         Day::test(Day::part1, "input", "2-20=01--0=0=0=2-120".to_owned())
     }
 
+    // Small xorshift generator so the property tests are reproducible
+    fn numbers(count: usize) -> Vec<i64> {
+        let mut state: u64 = 0x2545F4914F6CDD1D;
+        (0..count)
+            .map(|idx| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                // Mix in numbers of every magnitude, including the small ones
+                (state as i64) >> (idx % 64)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        for number in numbers(1000) {
+            let snafu = Snafu::from(number);
+            assert_eq!(snafu.to_i128(), Some(number as i128));
+            assert_eq!(snafu.to_string().parse::<Snafu>().unwrap(), snafu);
+        }
+    }
+    #[test]
+    fn arithmetic() {
+        let numbers = numbers(200);
+        for pair in numbers.windows(2) {
+            // Halve them so the results still fit
+            let (a, b) = (pair[0] >> 1, pair[1] >> 1);
+            let (x, y) = (Snafu::from(a), Snafu::from(b));
+            assert_eq!((x.clone() + y.clone()).to_i128(), Some(a as i128 + b as i128));
+            assert_eq!((x.clone() - y.clone()).to_i128(), Some(a as i128 - b as i128));
+            assert_eq!((x.clone() * y.clone()).to_i128(), Some(a as i128 * b as i128));
+            assert_eq!((-x).to_i128(), Some(-(a as i128)));
+        }
+    }
+    #[test]
+    fn other_bases() {
+        for number in numbers(1000) {
+            assert_eq!(Balanced::<3>::from(number).to_i128(), Some(number as i128));
+            assert_eq!(Balanced::<7>::from(number).to_i128(), Some(number as i128));
+            assert_eq!(Balanced::<255>::from(number).to_i128(), Some(number as i128));
+        }
+    }
+    #[test]
+    fn large_numbers() {
+        // Far beyond what fits in a machine integer
+        let big = "2".repeat(100).parse::<Snafu>().unwrap();
+        let sum = big.clone() + Snafu::from(1);
+        assert_eq!(sum.to_string(), format!("1{}", "=".repeat(100)));
+        assert_eq!(sum - Snafu::from(1), big);
+        assert_eq!(big.to_i128(), None);
+    }
+    #[test]
+    fn examples() {
+        for (decimal, snafu) in [(1, "1"), (3, "1="), (2022, "1=11-2"), (314159265, "1121-1110-1=0"), (0, "0"), (-3, "-2")] {
+            assert_eq!(Snafu::from(decimal).to_string(), snafu);
+            assert_eq!(snafu.parse::<Snafu>().unwrap().to_i128(), Some(decimal as i128));
+        }
+        assert!("12a".parse::<Snafu>().is_err());
+        assert!("".parse::<Snafu>().is_err());
+    }
+
     // Benchmarks
     extern crate test;
     #[bench]
@@ -34,6 +103,171 @@ mod tests {
     }
 }
 
+// -- Helpers --
+// Number in a balanced base, every digit is in -BASE/2..=BASE/2 so the base has to be odd.
+// Digits are stored least significant first without leading zeros, so zero has no digits. Every
+// digit has to fit in an i8, which limits the base to 255.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+struct Balanced<const BASE: i64> {
+    digits: Vec<i8>,
+}
+
+type Snafu = Balanced<5>;
+
+impl<const BASE: i64> Balanced<BASE> {
+    const MAX_DIGIT: i64 = BASE / 2;
+
+    // Turn a list of (possibly too large) digits into a valid number by propagating the carry
+    fn normalize(values: impl IntoIterator<Item = i64>) -> Self {
+        assert!(BASE % 2 == 1 && BASE >= 3, "Balanced numbers need an odd base");
+        assert!(BASE <= 255, "Balanced digits are stored as i8, the base can be at most 255");
+
+        let mut digits = Vec::new();
+        let mut carry = 0;
+        let mut values = values.into_iter();
+        loop {
+            let value = match values.next() {
+                Some(value) => value + carry,
+                None if carry != 0 => carry,
+                None => break,
+            };
+
+            let digit = (value + Self::MAX_DIGIT).rem_euclid(BASE) - Self::MAX_DIGIT;
+            carry = (value - digit) / BASE;
+            digits.push(digit as i8);
+        }
+
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+
+        Self { digits }
+    }
+
+    // None if the number does not fit
+    #[cfg(test)]
+    fn to_i128(&self) -> Option<i128> {
+        self.digits.iter().rev().try_fold(0_i128, |acc, &digit| {
+            acc.checked_mul(BASE as i128)?.checked_add(digit as i128)
+        })
+    }
+}
+
+impl<const BASE: i64> From<i64> for Balanced<BASE> {
+    fn from(value: i64) -> Self {
+        // Split into regular digits first, normalizing takes care of the balancing.
+        // Working with the absolute value avoids overflow for the smallest numbers
+        let sign = value.signum();
+        let mut rest = value.unsigned_abs();
+        let digits = iter::from_fn(|| {
+            (rest > 0).then(|| {
+                let digit = (rest % BASE as u64) as i64;
+                rest /= BASE as u64;
+                digit * sign
+            })
+        });
+
+        Self::normalize(digits.collect::<Vec<_>>())
+    }
+}
+
+impl<const BASE: i64> Add for Balanced<BASE> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self::Output {
+        let length = self.digits.len().max(other.digits.len());
+        let digit = |number: &Self, idx| *number.digits.get(idx).unwrap_or(&0) as i64;
+
+        Self::normalize((0..length).map(|idx| digit(&self, idx) + digit(&other, idx)))
+    }
+}
+
+impl<const BASE: i64> Neg for Balanced<BASE> {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        // Balanced digits are symmetric, so only the sign of every digit flips
+        Self {
+            digits: self.digits.into_iter().map(|digit| -digit).collect(),
+        }
+    }
+}
+
+impl<const BASE: i64> Sub for Balanced<BASE> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self::Output {
+        self + -other
+    }
+}
+
+impl<const BASE: i64> Mul for Balanced<BASE> {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self::Output {
+        if self.digits.is_empty() || other.digits.is_empty() {
+            return Self::default();
+        }
+
+        // Long multiplication, the carry is handled once at the end
+        let mut values = vec![0; self.digits.len() + other.digits.len()];
+        for (i, &a) in self.digits.iter().enumerate() {
+            for (j, &b) in other.digits.iter().enumerate() {
+                values[i + j] += a as i64 * b as i64;
+            }
+        }
+
+        Self::normalize(values)
+    }
+}
+
+impl FromStr for Snafu {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(anyhow!("Empty SNAFU number"));
+        }
+
+        let digits = s
+            .chars()
+            .rev()
+            .map(|c| match c {
+                '2' => Ok(2),
+                '1' => Ok(1),
+                '0' => Ok(0),
+                '-' => Ok(-1),
+                '=' => Ok(-2),
+                _ => Err(anyhow!("Invalid SNAFU digit '{c}'")),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self::normalize(digits))
+    }
+}
+
+impl fmt::Display for Snafu {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.digits.is_empty() {
+            return write!(f, "0");
+        }
+
+        for digit in self.digits.iter().rev() {
+            let c = match digit {
+                2 => '2',
+                1 => '1',
+                0 => '0',
+                -1 => '-',
+                -2 => '=',
+                _ => unreachable!("SNAFU digits are always in -2..=2"),
+            };
+            write!(f, "{c}")?;
+        }
+
+        Ok(())
+    }
+}
+
 // -- Solution --
 pub struct Day;
 impl aoc::Solver for Day {
@@ -45,49 +279,12 @@ impl aoc::Solver for Day {
     }
 
     fn part1(input: &str) -> Self::Output1 {
-        let mut sum: isize = input
+        input
             .trim()
             .lines()
-            .map(|line| {
-                line.chars().rev().enumerate().fold(0, |acc, (idx, c)| {
-                    let num = match c {
-                        '2' => 2,
-                        '1' => 1,
-                        '0' => 0,
-                        '-' => -1,
-                        '=' => -2,
-                        _ => panic!("Invalid number!"),
-                    };
-
-                    acc + num * 5_isize.pow(idx as u32)
-                })
-            }).sum();
-
-        let mut result = String::new();
-        while sum > 0 {
-            let mut carry = 0;
-            let rem = sum % 5;
-            match rem {
-                0 => result += "0",
-                1 => result += "1",
-                2 => result += "2",
-                3 => {
-                    result += "=";
-                    carry += 1;
-                },
-                4 => {
-                    result += "-";
-                    carry += 1;
-                },
-                _ => unreachable!("Number is mod 5 so should always be 0..=4"),
-            }
-
-            sum = sum/5 + carry;
-        }
-        // Reverse the string, since we construct it the wrong way around
-        result = result.chars().rev().collect();
-
-        result
+            .map(|line| line.parse::<Snafu>().unwrap())
+            .fold(Snafu::default(), |acc, number| acc + number)
+            .to_string()
     }
 
     fn part2(_input: &str) -> Self::Output2 {
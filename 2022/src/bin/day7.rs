@@ -1,11 +1,29 @@
 #![feature(test)]
-use anyhow::Result;
-use aoc::Solver;
-use implementation::Node;
+use core::fmt;
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, bail, Context, Result};
+use aoc::{Output, Solver};
 
 // -- Runners --
 fn main() -> Result<()> {
-    Day::solve()
+    Day::solve_with(&[
+        // Print the size of every directory instead of the answers
+        Output {
+            name: "du",
+            run: |input, _| Ok(FileSystem::replay(input)?.du()),
+        },
+        // Print the total size of a single file or directory
+        Output {
+            name: "lookup",
+            run: |input, path| {
+                let path = path.context("Missing path, use --lookup=<path>")?;
+                let fs = FileSystem::replay(input)?;
+                let id = fs.lookup(path).ok_or_else(|| anyhow!("'{path}' does not exist"))?;
+                Ok(format!("{}\t{}\n", fs.size(id), fs.path(id)))
+            },
+        },
+    ])
 }
 
 #[cfg(test)]
@@ -29,6 +47,44 @@ mod tests {
         Day::test(Day::part2, "input", 2568781)
     }
 
+    #[test]
+    fn tree_test1() -> Result<()> {
+        Day::test(|input| FileSystem::replay(input).unwrap().to_string(), "test-1", r"- / (dir)
+  - a (dir)
+    - e (dir)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir)
+    - d.ext (file, size=5626152)
+    - d.log (file, size=8033020)
+    - j (file, size=4060174)
+    - k (file, size=7214296)
+".to_owned())
+    }
+    #[test]
+    fn du_test1() -> Result<()> {
+        Day::test(|input| FileSystem::replay(input).unwrap().du(), "test-1", "48381165\t/\n94853\t/a\n24933642\t/d\n584\t/a/e\n".to_owned())
+    }
+    #[test]
+    fn lookup() -> Result<()> {
+        let fs = FileSystem::replay("$ cd /\n$ ls\ndir a\n$ cd a\n$ ls\n10 b")?;
+        assert_eq!(fs.lookup("/a/b").map(|id| fs.size(id)), Some(10));
+        assert_eq!(fs.lookup("/a/c"), None);
+
+        Ok(())
+    }
+    #[test]
+    fn inconsistent_transcript() {
+        assert!(FileSystem::replay("$ cd /\n$ cd a").is_err());
+        assert!(FileSystem::replay("$ cd /\n$ cd ..").is_err());
+        assert!(FileSystem::replay("$ cd /\ndir a").is_err());
+        assert!(FileSystem::replay("$ ls\n10 a\n$ ls\ndir a").is_err());
+    }
+
     // Benchmarks
     extern crate test;
     #[bench]
@@ -43,93 +99,207 @@ mod tests {
     }
 }
 
-mod implementation {
-    use std::collections::HashMap;
+#[derive(Debug)]
+enum Kind {
+    File(u32),
+    Directory(BTreeMap<String, usize>),
+}
+
+#[derive(Debug)]
+struct Node {
+    name: String,
+    parent: Option<usize>,
+    kind: Kind,
+}
+
+// Arena based directory tree, nodes refer to each other by index
+// The root directory is always stored at index 0
+#[derive(Debug)]
+struct FileSystem {
+    nodes: Vec<Node>,
+}
+
+impl FileSystem {
+    const ROOT: usize = 0;
 
-    pub struct Node {
-        size: u32,
-        children: HashMap<String, Self>,
-        parent: *mut Self,
+    fn new() -> Self {
+        let root = Node {
+            name: "/".to_owned(),
+            parent: None,
+            kind: Kind::Directory(BTreeMap::new()),
+        };
+
+        Self { nodes: vec![root] }
     }
 
-    impl Node {
-        pub fn default() -> Self {
-            Self {
-                size: 0,
-                children: HashMap::new(),
-                parent: std::ptr::null_mut(),
+    // Rebuild the file system from a transcript of cd and ls commands
+    fn replay(transcript: &str) -> Result<Self> {
+        let mut fs = Self::new();
+        let mut current = Self::ROOT;
+        let mut listing = false;
+
+        for (idx, line) in transcript.lines().enumerate() {
+            let context = || format!("Line {}: '{line}'", idx + 1);
+
+            // Any command ends the output of the previous ls
+            if line.starts_with("$ ") {
+                listing = false;
             }
-        }
 
-        pub fn new(input: &str) -> Self {
-            let mut root = Node::default();
-            let mut current = &mut root as *mut Node;
-
-            input
-                .lines()
-                .map(|line| line.rsplit_once(" ").unwrap())
-                .for_each(|split| {
-                    match split {
-                        ("$ cd", "/") => current = &mut root as *mut Node,
-                        ("$ cd", "..") => unsafe {
-                            let parent = (*current).parent;
-                            if parent == std::ptr::null_mut() {
-                                panic!("Node has no parent")
-                            }
-                            current = parent;
-                        },
-                        ("$ cd", name) => unsafe {
-                            current = (*current).get_directory(name).unwrap();
-                        },
-                        ("$", "ls") => {},
-                        ("dir", name) => unsafe {
-                            (*current).add_directory(name.to_owned())
-                        },
-                        (size, _name) => unsafe {
-                            (*current).add_file(size.parse().unwrap())
-                        },
-                    }
-                });
-
-            root
+            match line.split(' ').collect::<Vec<_>>().as_slice() {
+                ["$", "cd", "/"] => current = Self::ROOT,
+                ["$", "cd", ".."] => {
+                    current = fs.nodes[current]
+                        .parent
+                        .ok_or_else(|| anyhow!("Root directory has no parent"))
+                        .with_context(context)?
+                }
+                ["$", "cd", name] => {
+                    current = fs
+                        .child(current, name)
+                        .filter(|&child| fs.is_directory(child))
+                        .ok_or_else(|| anyhow!("Unknown directory '{name}' in '{}'", fs.path(current)))
+                        .with_context(context)?
+                }
+                ["$", "ls"] => listing = true,
+                ["$", ..] => bail!("{}: Unknown command", context()),
+                _ if !listing => bail!("{}: Output without ls", context()),
+                ["dir", name] => {
+                    fs.insert(current, name, Kind::Directory(BTreeMap::new()))
+                        .with_context(context)?;
+                }
+                [size, name] => {
+                    let size = size.parse().with_context(context)?;
+                    fs.insert(current, name, Kind::File(size))
+                        .with_context(context)?;
+                }
+                _ => bail!("{}: Invalid output", context()),
+            }
         }
 
-        fn add_file(&mut self, size: u32) {
-            self.size += size;
+        Ok(fs)
+    }
+
+    // Add a new node to a directory, listing the same entry again is allowed as long as it matches
+    fn insert(&mut self, parent: usize, name: &str, kind: Kind) -> Result<usize> {
+        if let Some(existing) = self.child(parent, name) {
+            return match (&self.nodes[existing].kind, &kind) {
+                (Kind::Directory(_), Kind::Directory(_)) => Ok(existing),
+                (Kind::File(a), Kind::File(b)) if a == b => Ok(existing),
+                _ => Err(anyhow!("'{}' conflicts with earlier listing", self.path(existing))),
+            };
         }
 
-        fn add_directory(&mut self, name: String) {
-            let mut child = Self::default();
-            child.parent = self as *mut Self;
-            self.children.insert(name, child);
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            name: name.to_owned(),
+            parent: Some(parent),
+            kind,
+        });
+
+        match &mut self.nodes[parent].kind {
+            Kind::Directory(children) => children.insert(name.to_owned(), id),
+            Kind::File(_) => unreachable!("Files can not have children"),
+        };
+
+        Ok(id)
+    }
+
+    fn child(&self, id: usize, name: &str) -> Option<usize> {
+        match &self.nodes[id].kind {
+            Kind::Directory(children) => children.get(name).copied(),
+            Kind::File(_) => None,
         }
+    }
+
+    fn is_directory(&self, id: usize) -> bool {
+        matches!(self.nodes[id].kind, Kind::Directory(_))
+    }
+
+    // Find a node by its absolute path, e.g. /a/e
+    fn lookup(&self, path: &str) -> Option<usize> {
+        path.split('/')
+            .filter(|part| !part.is_empty())
+            .try_fold(Self::ROOT, |id, name| self.child(id, name))
+    }
 
-        fn get_directory(&mut self, name: &str) -> Option<&mut Self> {
-            self.children.get_mut(name)
+    fn path(&self, id: usize) -> String {
+        match self.nodes[id].parent {
+            None => "/".to_owned(),
+            Some(Self::ROOT) => format!("/{}", self.nodes[id].name),
+            Some(parent) => format!("{}/{}", self.path(parent), self.nodes[id].name),
         }
+    }
 
-        pub fn get_size(&self) -> u32 {
-            let mut size = self.size;
+    // Total size of every node, including everything contained in directories
+    fn sizes(&self) -> Vec<u32> {
+        let mut sizes = self.nodes.iter().map(|node| match node.kind {
+            Kind::File(size) => size,
+            Kind::Directory(_) => 0,
+        }).collect::<Vec<_>>();
 
-            for (_, node) in &self.children {
-                size += node.get_size();
-            }
+        // Children are always created after their parent, so by going backwards every node is
+        // complete before it is added to its parent
+        for (id, node) in self.nodes.iter().enumerate().skip(1).rev() {
+            sizes[node.parent.unwrap()] += sizes[id];
+        }
 
-            size
+        sizes
+    }
+
+    fn size(&self, id: usize) -> u32 {
+        match &self.nodes[id].kind {
+            Kind::File(size) => *size,
+            Kind::Directory(children) => children.values().map(|&child| self.size(child)).sum(),
         }
+    }
 
-        pub fn flatten_sizes(&self) -> Vec<u32> {
-            let mut dirs = vec![self.get_size()];
+    fn directory_sizes(&self) -> impl Iterator<Item = u32> + '_ {
+        self.sizes()
+            .into_iter()
+            .enumerate()
+            .filter(|&(id, _)| self.is_directory(id))
+            .map(|(_, size)| size)
+    }
 
-            for (_, node) in &self.children {
-                dirs.extend(node.flatten_sizes())
-            }
+    // Size and path of every directory, similar to the output of du
+    fn du(&self) -> String {
+        let sizes = self.sizes();
+
+        (0..self.nodes.len())
+            .filter(|&id| self.is_directory(id))
+            .map(|id| format!("{}\t{}\n", sizes[id], self.path(id)))
+            .collect()
+    }
+
+    // Tree rendering in the same format as the puzzle description
+    fn tree(&self) -> String {
+        let mut output = String::new();
+        self.render(Self::ROOT, 0, &mut output);
+        output
+    }
 
-            dirs
+    fn render(&self, id: usize, depth: usize, output: &mut String) {
+        let node = &self.nodes[id];
+        let indent = "  ".repeat(depth);
+        match &node.kind {
+            Kind::File(size) => *output += &format!("{indent}- {} (file, size={size})\n", node.name),
+            Kind::Directory(children) => {
+                *output += &format!("{indent}- {} (dir)\n", node.name);
+                for &child in children.values() {
+                    self.render(child, depth + 1, output);
+                }
+            }
         }
     }
 }
 
+impl fmt::Display for FileSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.tree())
+    }
+}
+
 // -- Solution --
 pub struct Day;
 impl aoc::Solver for Day {
@@ -140,26 +310,20 @@ impl aoc::Solver for Day {
     }
 
     fn part1(input: &str) -> Self::Output1 {
-        Node::new(input)
-            .flatten_sizes()
-            .iter()
-            .filter(|&&size| size < 100000)
+        FileSystem::replay(input)
+            .unwrap()
+            .directory_sizes()
+            .filter(|&size| size < 100000)
             .sum()
     }
 
     fn part2(input: &str) -> Self::Output2 {
-        let root = Node::new(input);
-        let need_to_free = root.get_size() - 40000000;
-
-        let mut sizes = root.flatten_sizes();
-        sizes.sort();
-        sizes.iter()
-            .find_map(|&size| {
-            if size > need_to_free {
-                Some(size)
-            } else {
-                None
-            }
-        }).unwrap()
+        let fs = FileSystem::replay(input).unwrap();
+        let need_to_free = fs.size(FileSystem::ROOT) - 40000000;
+
+        fs.directory_sizes()
+            .filter(|&size| size > need_to_free)
+            .min()
+            .unwrap()
     }
 }
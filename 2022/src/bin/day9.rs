@@ -2,12 +2,26 @@
 use core::fmt;
 use std::{collections::HashSet, str::FromStr};
 
-use anyhow::Result;
-use aoc::Solver;
+use anyhow::{Context, Result};
+use aoc::{
+    visualize::{Color, Render, Visualizer},
+    Output, Solver,
+};
 
 // -- Runners --
 fn main() -> Result<()> {
-    Day::solve()
+    Day::solve_with(&[
+        // Print the first steps of the long rope as text, all of them if no count is given
+        Output {
+            name: "frames",
+            run: |input, steps| frames(input, steps, chase),
+        },
+        // Same, but every knot moves into the spot the knot in front of it just left
+        Output {
+            name: "snake",
+            run: |input, steps| frames(input, steps, drag),
+        },
+    ])
 }
 
 #[cfg(test)]
@@ -35,6 +49,39 @@ mod tests {
         Day::test(Day::part2, "input", 2434)
     }
 
+    #[test]
+    fn every_knot_visited() -> Result<()> {
+        Day::test(|input| {
+            let mut rope = Rope::new(10);
+            simulate(&mut rope, input);
+            rope.visited.iter().map(|visited| visited.len()).collect::<Vec<_>>()
+        }, "test-2", vec![96, 88, 80, 72, 64, 56, 50, 46, 41, 36])
+    }
+    #[test]
+    fn diagonal_head() {
+        let mut rope = Rope::new(3);
+        rope.record();
+        for _ in 0..3 {
+            rope.step(Direction::UpRight);
+        }
+
+        assert_eq!(rope.elements, vec![Position::new(3, 3), Position::new(2, 2), Position::new(1, 1)]);
+        assert_eq!(rope.frames_text().last().unwrap(), "...H\n..1.\n.T..\n#...\n");
+    }
+    #[test]
+    fn frames_output() -> Result<()> {
+        Day::test(|input| frames(input, Some("1"), chase).unwrap(), "test-1", "H.\n\n1H\n".to_owned())
+    }
+    #[test]
+    fn drag_rule() {
+        let mut rope = Rope::with_rule(3, drag);
+        for direction in [Direction::Right, Direction::Right, Direction::Up, Direction::Up] {
+            rope.step(direction);
+        }
+
+        assert_eq!(rope.elements, vec![Position::new(2, 2), Position::new(2, 1), Position::new(1, 0)]);
+    }
+
     // Benchmarks
     extern crate test;
     #[bench]
@@ -72,6 +119,25 @@ enum Direction {
     Right,
     Down,
     Left,
+    UpRight,
+    UpLeft,
+    DownRight,
+    DownLeft,
+}
+
+impl Direction {
+    fn offset(&self) -> (i32, i32) {
+        match self {
+            Direction::Up => (0, 1),
+            Direction::Right => (1, 0),
+            Direction::Down => (0, -1),
+            Direction::Left => (-1, 0),
+            Direction::UpRight => (1, 1),
+            Direction::UpLeft => (-1, 1),
+            Direction::DownRight => (1, -1),
+            Direction::DownLeft => (-1, -1),
+        }
+    }
 }
 
 impl FromStr for Direction {
@@ -83,125 +149,251 @@ impl FromStr for Direction {
             "R" => Ok(Direction::Right),
             "D" => Ok(Direction::Down),
             "L" => Ok(Direction::Left),
+            "UR" | "RU" => Ok(Direction::UpRight),
+            "UL" | "LU" => Ok(Direction::UpLeft),
+            "DR" | "RD" => Ok(Direction::DownRight),
+            "DL" | "LD" => Ok(Direction::DownLeft),
             _ => Err(anyhow::anyhow!("Invalid input")),
         }
     }
 }
 
+// Decides where a knot moves to, gets the current position of the knot and the position of the
+// knot in front of it before and after it moved
+type Rule = fn(tail: Position, before: Position, after: Position) -> Position;
+
+// The rule from the puzzle, move one step towards the knot in front (diagonally if needed)
+fn chase(mut tail: Position, _before: Position, head: Position) -> Position {
+    if tail.distance(&head) > 1 {
+        tail.x += (head.x - tail.x).signum();
+        tail.y += (head.y - tail.y).signum();
+    }
+
+    tail
+}
+
+// Move into the spot the knot in front just left, like the body of a snake
+fn drag(tail: Position, before: Position, after: Position) -> Position {
+    if tail.distance(&after) > 1 {
+        before
+    } else {
+        tail
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Bounds {
+    xmin: i32,
+    xmax: i32,
+    ymin: i32,
+    ymax: i32,
+}
+
+impl Bounds {
+    fn new<'a>(positions: impl Iterator<Item = &'a Position>) -> Self {
+        let mut bounds = Self { xmin: 0, xmax: 0, ymin: 0, ymax: 0 };
+        for position in positions {
+            bounds.xmin = bounds.xmin.min(position.x);
+            bounds.xmax = bounds.xmax.max(position.x);
+            bounds.ymin = bounds.ymin.min(position.y);
+            bounds.ymax = bounds.ymax.max(position.y);
+        }
+
+        bounds
+    }
+}
+
 #[derive(Debug)]
 struct Rope {
     elements: Vec<Position>,
-    visited: HashSet<Position>,
+    // Positions visited by every knot
+    visited: Vec<HashSet<Position>>,
+    rule: Rule,
+    // Position of every knot after each step, only recorded when enabled
+    frames: Option<Vec<Vec<Position>>>,
 }
 
 impl Rope {
     fn new(length: usize) -> Self {
+        Self::with_rule(length, chase)
+    }
+
+    fn with_rule(length: usize, rule: Rule) -> Self {
+        assert!(length > 0, "Rope needs at least one knot");
+
         let start = Position::new(0, 0);
-        let mut visited = HashSet::new();
-        visited.insert(start);
+        let elements = vec![start; length];
+        let visited = vec![HashSet::from([start]); length];
 
-        let mut elements = Vec::new();
-        for _ in 0..length {
-            elements.push(start);
-        }
+        Self { elements, visited, rule, frames: None }
+    }
 
-        Self {elements, visited}
+    // Start recording the position of every knot after each step
+    fn record(&mut self) {
+        self.frames = Some(vec![self.elements.clone()]);
     }
 
     fn step(&mut self, d: Direction) {
-        // Get a &mut to the first element
-        let mut head = self.elements.first_mut().unwrap();
-
-        // Update the first element
-        match d {
-            Direction::Up => head.y += 1,
-            Direction::Right => head.x += 1,
-            Direction::Down => head.y -= 1,
-            Direction::Left => head.x -= 1,
-        }
+        let (dx, dy) = d.offset();
 
-        // Head is now a copy of the position of the first element
-        let mut head = *head;
+        let mut before = self.elements[0];
+        self.elements[0].x += dx;
+        self.elements[0].y += dy;
 
         // Update all remaining elements of the rope
-        for tail in self.elements.iter_mut().skip(1) {
-            // Check if tail is touching the head
-            if tail.distance(&head) > 1 {
-                if tail.x < head.x {
-                    tail.x += 1;
-                } else if tail.x > head.x {
-                    tail.x -= 1;
-                }
-
-                if tail.y < head.y {
-                    tail.y += 1;
-                } else if tail.y > head.y {
-                    tail.y -= 1;
-                }
+        for i in 1..self.elements.len() {
+            let tail = self.elements[i];
+            self.elements[i] = (self.rule)(tail, before, self.elements[i - 1]);
+            before = tail;
+        }
+
+        for (visited, knot) in self.visited.iter_mut().zip(self.elements.iter()) {
+            visited.insert(*knot);
+        }
+
+        if let Some(frames) = self.frames.as_mut() {
+            frames.push(self.elements.clone());
+        }
+    }
+
+    fn tail_visited(&self) -> &HashSet<Position> {
+        self.visited.last().unwrap()
+    }
+
+    fn bounds(&self) -> Bounds {
+        Bounds::new(self.visited.iter().flatten())
+    }
+
+    // Character used to draw a position, knots are drawn over the trail of the tail
+    fn cell(knots: &[Position], trail: &HashSet<Position>, pos: Position) -> char {
+        match knots.iter().position(|&p| p == pos) {
+            Some(0) => 'H',
+            Some(index) if index == knots.len() - 1 => 'T',
+            Some(index) => char::from_digit(index as u32 % 10, 10).unwrap(),
+            None if trail.contains(&pos) => '#',
+            None => '.',
+        }
+    }
+
+    fn draw(knots: &[Position], trail: &HashSet<Position>, bounds: Bounds) -> String {
+        let mut output = String::new();
+        for y in (bounds.ymin..=bounds.ymax).rev() {
+            for x in bounds.xmin..=bounds.xmax {
+                output.push(Self::cell(knots, trail, Position::new(x, y)));
             }
+            output.push('\n');
+        }
+
+        output
+    }
+
+    // Text rendering of every recorded frame, all frames share the same bounds
+    fn frames_text(&self) -> Vec<String> {
+        let bounds = self.bounds();
+        let mut trail = HashSet::new();
+
+        self.frames
+            .iter()
+            .flatten()
+            .map(|knots| {
+                trail.insert(*knots.last().unwrap());
+                Self::draw(knots, &trail, bounds)
+            })
+            .collect()
+    }
 
-            head = *tail;
+    // Render every recorded frame, all frames share the same bounds
+    fn animate(&self, visualizer: &mut Visualizer) -> Result<()> {
+        let bounds = self.bounds();
+        let mut trail = HashSet::new();
+
+        for knots in self.frames.iter().flatten() {
+            trail.insert(*knots.last().unwrap());
+            visualizer.frame(&RopeFrame { knots, trail: &trail, bounds })?;
         }
 
-        // Mark the location of the last element as visited
-        self.visited.insert(*self.elements.last().unwrap());
+        Ok(())
+    }
+}
+
+struct RopeFrame<'a> {
+    knots: &'a [Position],
+    trail: &'a HashSet<Position>,
+    bounds: Bounds,
+}
 
+impl Render for RopeFrame<'_> {
+    fn size(&self) -> (usize, usize) {
+        let width = self.bounds.xmax - self.bounds.xmin + 1;
+        let height = self.bounds.ymax - self.bounds.ymin + 1;
+        (width as usize, height as usize)
+    }
+
+    fn cell(&self, x: usize, y: usize) -> u8 {
+        let position = Position::new(x as i32 + self.bounds.xmin, self.bounds.ymax - y as i32);
+        match Rope::cell(self.knots, self.trail, position) {
+            '.' => 0,
+            '#' => 1,
+            'H' => 3,
+            _ => 2,
+        }
+    }
+
+    fn palette(&self) -> &[Color] {
+        // Background, trail, knots and head
+        &[[0xFF, 0xFF, 0xFF], [0xC0, 0xC0, 0xC0], [0x00, 0x00, 0x00], [0xFF, 0x00, 0x00]]
     }
 }
 
 impl fmt::Display for Rope {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        // @TODO Automatically pick the right value for this
-        let xmin = -15;
-        let xmax = 15;
-        let ymin = -10;
-        let ymax = 10;
-
-        for y in (ymin..ymax).rev() {
-            for x in xmin..xmax {
-                let pos = Position::new(x, y);
-                let c;
-                if *self.elements.first().unwrap() == pos {
-                    c = 'H';
-                } else if self.elements.contains(&pos) {
-                    let index = self.elements.iter().position(|p| *p == pos).unwrap();
-                    c = char::from(index as u8 + b'0');
-                } else if self.visited.contains(&pos) {
-                    c = '#';
-                } else {
-                    c = '.'
-                }
-
-                write!(f, "{}", c)?;
-            }
-            writeln!(f, "")?;
-        }
-
-        writeln!(f, "")
+        write!(f, "{}", Self::draw(&self.elements, self.tail_visited(), self.bounds()))
     }
 }
 
 fn parse(input: &str) -> Vec<(Direction, usize)> {
     input
         .lines()
-        .map(|line| line.split_once(" ").unwrap())
+        .map(|line| line.split_once(' ').unwrap())
         .map(|(d, c)| (d.parse().unwrap(), c.parse().unwrap()))
         .collect()
 }
 
-fn solution(input: &str, length: usize) -> usize {
-    let instructions = parse(input);
+fn simulate(rope: &mut Rope, input: &str) {
+    for (direction, count) in parse(input) {
+        for _ in 0..count {
+            rope.step(direction);
+        }
+    }
+}
+
+// Text frames of the first steps of a rope with 10 knots, separated by empty lines
+fn frames(input: &str, steps: Option<&str>, rule: Rule) -> Result<String> {
+    let steps = steps.map_or(Ok(usize::MAX), |steps| steps.parse().with_context(|| format!("Invalid number of steps '{steps}'")))?;
+    let mut rope = Rope::with_rule(10, rule);
+    rope.record();
 
-    let mut rope = Rope::new(length);
+    let directions = parse(input).into_iter().flat_map(|(direction, count)| std::iter::repeat_n(direction, count));
+    for direction in directions.take(steps) {
+        rope.step(direction);
+    }
 
-    for instruction in instructions {
-        for _ in 0..instruction.1 {
-            rope.step(instruction.0);
-        }
+    Ok(rope.frames_text().join("\n"))
+}
+
+fn solution(input: &str, length: usize, visualizer: Option<Visualizer>) -> usize {
+    let mut rope = Rope::new(length);
+    if visualizer.is_some() {
+        rope.record();
     }
 
+    simulate(&mut rope, input);
+
+    if let Some(mut visualizer) = visualizer {
+        rope.animate(&mut visualizer).unwrap();
+    }
 
-    rope.visited.len()
+    rope.tail_visited().len()
 }
 
 // -- Solution --
@@ -214,10 +406,10 @@ impl aoc::Solver for Day {
     }
 
     fn part1(input: &str) -> Self::Output1 {
-        solution(input, 2)
+        solution(input, 2, Visualizer::from_options(Self::day(), 1).map(|v| v.scale(4)))
     }
 
     fn part2(input: &str) -> Self::Output2 {
-        solution(input, 10)
+        solution(input, 10, Visualizer::from_options(Self::day(), 2).map(|v| v.scale(4)))
     }
 }
@@ -0,0 +1,197 @@
+use core::fmt;
+use std::{collections::HashSet, fmt::Debug, str::FromStr};
+
+use anyhow::{Context, Result};
+
+/// An instruction set that can be run by [Machine]
+pub trait InstructionSet: FromStr<Err = anyhow::Error> + fmt::Display + Debug {
+    /// The register file of the machine
+    type Registers: Default + Clone + Debug;
+
+    /// Number of cycles it takes to execute the instruction
+    fn cycles(&self) -> usize;
+
+    /// Execute the instruction, this happens at the end of the last cycle of the instruction.
+    /// Returns the new program counter if the instruction jumps, [None] otherwise
+    fn execute(&self, pc: usize, registers: &mut Self::Registers) -> Option<usize>;
+}
+
+/// Parse a program, one instruction per line
+pub fn parse<I: InstructionSet>(input: &str) -> Result<Vec<I>> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| line.parse().with_context(|| format!("Failed to parse line {}: '{line}'", idx + 1)))
+        .collect()
+}
+
+/// Reason the machine stopped running
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Halt {
+    /// The program counter moved past the end of the program
+    Finished,
+    /// The program counter hit a breakpoint, the instruction at this address has not been executed yet
+    Breakpoint(usize),
+}
+
+/// A single entry in the execution trace
+#[derive(Debug, Clone)]
+pub struct Trace<R> {
+    /// Cycle on which the instruction started
+    pub cycle: usize,
+    pub pc: usize,
+    pub instruction: String,
+    /// Registers after executing the instruction
+    pub registers: R,
+}
+
+impl<R: Debug> fmt::Display for Trace<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>6} {:04} {:<16} {:?}", self.cycle, self.pc, self.instruction, self.registers)
+    }
+}
+
+type Hook<'a, R> = Box<dyn FnMut(usize, &R) + 'a>;
+
+/// Generic fetch/execute loop for register machines
+pub struct Machine<'a, I: InstructionSet> {
+    program: Vec<I>,
+    pc: usize,
+    // The cycle that is currently about to start (starts at 1)
+    cycle: usize,
+    registers: I::Registers,
+    hooks: Vec<Hook<'a, I::Registers>>,
+    breakpoints: HashSet<usize>,
+    // Breakpoint that the last run stopped on, it is skipped once so that run can resume
+    resumed_from: Option<usize>,
+    trace: Option<Vec<Trace<I::Registers>>>,
+}
+
+impl<'a, I: InstructionSet> Machine<'a, I> {
+    pub fn new(program: Vec<I>) -> Self {
+        Self {
+            program,
+            pc: 0,
+            cycle: 1,
+            registers: Default::default(),
+            hooks: Vec::new(),
+            breakpoints: HashSet::new(),
+            resumed_from: None,
+            trace: None,
+        }
+    }
+
+    pub fn load(input: &str) -> Result<Self> {
+        Ok(Self::new(parse(input)?))
+    }
+
+    /// Register a hook that gets called during every cycle with the cycle number and the registers
+    /// as they are during that cycle (so NOT the value after the instruction finished)
+    pub fn on_cycle(&mut self, hook: impl FnMut(usize, &I::Registers) + 'a) -> &mut Self {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    pub fn add_breakpoint(&mut self, pc: usize) -> &mut Self {
+        self.breakpoints.insert(pc);
+        self
+    }
+
+    pub fn remove_breakpoint(&mut self, pc: usize) -> &mut Self {
+        self.breakpoints.remove(&pc);
+        self
+    }
+
+    /// Start recording every executed instruction
+    pub fn enable_trace(&mut self) -> &mut Self {
+        self.trace.get_or_insert_with(Vec::new);
+        self
+    }
+
+    pub fn trace(&self) -> &[Trace<I::Registers>] {
+        self.trace.as_deref().unwrap_or_default()
+    }
+
+    pub fn registers(&self) -> &I::Registers {
+        &self.registers
+    }
+
+    pub fn registers_mut(&mut self) -> &mut I::Registers {
+        &mut self.registers
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// The cycle that will start when the next instruction is executed
+    pub fn cycle(&self) -> usize {
+        self.cycle
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.pc >= self.program.len()
+    }
+
+    /// Execute a single instruction, returns false if there was nothing left to execute
+    pub fn step(&mut self) -> bool {
+        let Some(instruction) = self.program.get(self.pc) else {
+            return false;
+        };
+
+        self.resumed_from = None;
+
+        let start = self.cycle;
+        for _ in 0..instruction.cycles() {
+            for hook in self.hooks.iter_mut() {
+                hook(self.cycle, &self.registers);
+            }
+            self.cycle += 1;
+        }
+
+        let pc = self.pc;
+        self.pc = instruction.execute(pc, &mut self.registers).unwrap_or(pc + 1);
+
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(Trace {
+                cycle: start,
+                pc,
+                instruction: instruction.to_string(),
+                registers: self.registers.clone(),
+            });
+        }
+
+        true
+    }
+
+    /// Run until the program finishes or a breakpoint is hit.
+    /// The breakpoint that the previous run stopped on is ignored so that calling run again
+    /// resumes execution
+    pub fn run(&mut self) -> Halt {
+        loop {
+            if self.is_finished() {
+                return Halt::Finished;
+            }
+
+            if self.breakpoints.contains(&self.pc) && self.resumed_from != Some(self.pc) {
+                self.resumed_from = Some(self.pc);
+                return Halt::Breakpoint(self.pc);
+            }
+
+            self.step();
+        }
+    }
+
+    /// Listing of the loaded program with addresses
+    pub fn disassemble(&self) -> String {
+        disassemble(&self.program)
+    }
+}
+
+pub fn disassemble<I: InstructionSet>(program: &[I]) -> String {
+    program
+        .iter()
+        .enumerate()
+        .map(|(pc, instruction)| format!("{pc:04}: {instruction}\n"))
+        .collect()
+}
@@ -0,0 +1,94 @@
+use anyhow::{anyhow, Result};
+
+// Glyphs are stored row by row, using '#' for lit pixels
+struct Font {
+    width: usize,
+    height: usize,
+    // Horizontal distance between the start of two consecutive letters
+    pitch: usize,
+    glyphs: &'static [(char, &'static str)],
+}
+
+const SMALL: Font = Font {
+    width: 4,
+    height: 6,
+    pitch: 5,
+    glyphs: &[
+        ('A', ".##.#..##..######..##..#"),
+        ('B', "###.#..####.#..##..####."),
+        ('C', ".##.#..##...#...#..#.##."),
+        ('E', "#####...###.#...#...####"),
+        ('F', "#####...###.#...#...#..."),
+        ('G', ".##.#..##...#.###..#.###"),
+        ('H', "#..##..######..##..##..#"),
+        ('I', ".###..#...#...#...#..###"),
+        ('J', "..##...#...#...##..#.##."),
+        ('K', "#..##.#.##..#.#.#.#.#..#"),
+        ('L', "#...#...#...#...#...####"),
+        ('O', ".##.#..##..##..##..#.##."),
+        ('P', "###.#..##..####.#...#..."),
+        ('R', "###.#..##..####.#.#.#..#"),
+        ('S', ".####...#....##....####."),
+        ('U', "#..##..##..##..##..#.##."),
+        ('Y', "#...#....#.#..#...#...#."),
+        ('Z', "####...#..#..#..#...####"),
+    ],
+};
+
+const LARGE: Font = Font {
+    width: 6,
+    height: 10,
+    pitch: 8,
+    glyphs: &[
+        ('A', "..##...#..#.#....##....##....########....##....##....##....#"),
+        ('B', "#####.#....##....##....######.#....##....##....##....######."),
+        ('C', ".####.#....##.....#.....#.....#.....#.....#.....#....#.####."),
+        ('E', "#######.....#.....#.....#####.#.....#.....#.....#.....######"),
+        ('F', "#######.....#.....#.....#####.#.....#.....#.....#.....#....."),
+        ('G', ".####.#....##.....#.....#.....#..####....##....##...##.###.#"),
+        ('H', "#....##....##....##....########....##....##....##....##....#"),
+        ('J', "...###....#.....#.....#.....#.....#.....#.#...#.#...#..###.."),
+        ('K', "#....##...#.#..#..#.#...##....##....#.#...#..#..#...#.#....#"),
+        ('L', "#.....#.....#.....#.....#.....#.....#.....#.....#.....######"),
+        ('N', "#....###...###...##.#..##.#..##..#.##..#.##...###...###....#"),
+        ('P', "#####.#....##....##....######.#.....#.....#.....#.....#....."),
+        ('R', "#####.#....##....##....######.#..#..#...#.#...#.#....##....#"),
+        ('X', "#....##....#.#..#..#..#...##....##...#..#..#..#.#....##....#"),
+        ('Z', "######.....#.....#....#....#....#....#....#.....#.....######"),
+    ],
+};
+
+/// Convert letters drawn with '#' (lit) and any other character (unlit) into a string.
+/// Both the 4x6 and 6x10 puzzle fonts are supported, the font is selected based on the height of
+/// the image.
+pub fn recognize(image: &str) -> Result<String> {
+    let rows = image
+        .lines()
+        .map(|line| line.chars().map(|c| c == '#').collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    let font = [&SMALL, &LARGE]
+        .into_iter()
+        .find(|font| font.height == rows.len())
+        .ok_or_else(|| anyhow!("No font with height {}", rows.len()))?;
+
+    let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let pixel = |x: usize, y: usize| rows[y].get(x).copied().unwrap_or(false);
+
+    (0..width)
+        .step_by(font.pitch)
+        .enumerate()
+        .map(|(idx, start)| {
+            let glyph = (0..font.height)
+                .flat_map(|y| (start..start + font.width).map(move |x| (x, y)))
+                .map(|(x, y)| if pixel(x, y) { '#' } else { '.' })
+                .collect::<String>();
+
+            font.glyphs
+                .iter()
+                .find(|(_, pattern)| *pattern == glyph)
+                .map(|(letter, _)| *letter)
+                .ok_or_else(|| anyhow!("Unknown letter at position {idx}"))
+        })
+        .collect()
+}
@@ -0,0 +1,207 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::atomic::{AtomicI64, Ordering},
+    thread,
+};
+
+/// A maximization problem that can be solved using [Search]
+pub trait Problem: Sync {
+    type State: Clone + Send;
+    type Key: Hash + Eq;
+
+    /// Score of a state, the search looks for the state with the highest score
+    fn score(&self, state: &Self::State) -> i64;
+
+    /// All states that can be reached from this state in a single move
+    fn moves(&self, state: &Self::State) -> Vec<Self::State>;
+
+    /// Upper bound on the score of any state that can be reached from this state, branches that
+    /// can not improve on the best score so far are pruned
+    fn bound(&self, _state: &Self::State) -> i64 {
+        i64::MAX
+    }
+
+    /// States with the same key have the same future, so a state is skipped if a state with the
+    /// same key and at least the same score has already been explored
+    fn key(&self, _state: &Self::State) -> Option<Self::Key> {
+        None
+    }
+}
+
+/// Best state that was found, including every state leading up to it
+#[derive(Debug, Clone)]
+pub struct Solution<S> {
+    pub score: i64,
+    /// Starts with the initial state and ends with the best state
+    pub path: Vec<S>,
+}
+
+/// Branch-and-bound search for the best state
+pub struct Search<'a, P: Problem> {
+    problem: &'a P,
+    beam: Option<usize>,
+    parallel: bool,
+}
+
+impl<'a, P: Problem> Search<'a, P> {
+    pub fn new(problem: &'a P) -> Self {
+        Self {
+            problem,
+            beam: None,
+            parallel: false,
+        }
+    }
+
+    /// Only keep the given number of most promising states (by bound) at every depth.
+    /// This is no longer guaranteed to find the optimal solution.
+    pub fn beam(mut self, width: usize) -> Self {
+        self.beam = Some(width);
+        self
+    }
+
+    /// Search every move from the initial state on a separate thread
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    pub fn run(&self, initial: P::State) -> Solution<P::State> {
+        match self.beam {
+            Some(width) => self.beam_search(initial, width),
+            None if self.parallel => self.parallel_search(initial),
+            None => {
+                let best = AtomicI64::new(i64::MIN);
+                let mut worker = Worker::new(self.problem, &best);
+                worker.visit(initial);
+                worker.solution.unwrap()
+            }
+        }
+    }
+
+    fn parallel_search(&self, initial: P::State) -> Solution<P::State> {
+        let best = AtomicI64::new(self.problem.score(&initial));
+        let mut solution = Solution {
+            score: self.problem.score(&initial),
+            path: vec![initial.clone()],
+        };
+
+        let results = thread::scope(|scope| {
+            let handles = self
+                .problem
+                .moves(&initial)
+                .into_iter()
+                .map(|root| {
+                    let best = &best;
+                    let initial = initial.clone();
+                    scope.spawn(move || {
+                        let mut worker = Worker::new(self.problem, best);
+                        worker.path.push(initial);
+                        worker.visit(root);
+                        worker.solution
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        // Pick the first best result to keep the result deterministic
+        for result in results.into_iter().flatten() {
+            if result.score > solution.score {
+                solution = result;
+            }
+        }
+
+        solution
+    }
+
+    fn beam_search(&self, initial: P::State, width: usize) -> Solution<P::State> {
+        let mut solution = Solution {
+            score: self.problem.score(&initial),
+            path: vec![initial.clone()],
+        };
+
+        // Every entry keeps the full path to the state
+        let mut layer = vec![vec![initial]];
+        while !layer.is_empty() {
+            let mut next = Vec::new();
+            for path in layer {
+                for state in self.problem.moves(path.last().unwrap()) {
+                    if self.problem.bound(&state) <= solution.score {
+                        continue;
+                    }
+
+                    let mut path = path.clone();
+                    path.push(state);
+                    next.push(path);
+                }
+            }
+
+            for path in next.iter() {
+                let score = self.problem.score(path.last().unwrap());
+                if score > solution.score {
+                    solution = Solution { score, path: path.clone() };
+                }
+            }
+
+            next.sort_by_cached_key(|path| std::cmp::Reverse(self.problem.bound(path.last().unwrap())));
+            next.truncate(width);
+            layer = next;
+        }
+
+        solution
+    }
+}
+
+// Depth first search on a single thread, the best score is shared between threads for pruning
+struct Worker<'a, P: Problem> {
+    problem: &'a P,
+    best: &'a AtomicI64,
+    memo: HashMap<P::Key, i64>,
+    path: Vec<P::State>,
+    solution: Option<Solution<P::State>>,
+}
+
+impl<'a, P: Problem> Worker<'a, P> {
+    fn new(problem: &'a P, best: &'a AtomicI64) -> Self {
+        Self {
+            problem,
+            best,
+            memo: HashMap::new(),
+            path: Vec::new(),
+            solution: None,
+        }
+    }
+
+    fn visit(&mut self, state: P::State) {
+        let score = self.problem.score(&state);
+        self.path.push(state);
+
+        if self.solution.as_ref().is_none_or(|solution| score > solution.score) {
+            self.solution = Some(Solution { score, path: self.path.clone() });
+            self.best.fetch_max(score, Ordering::Relaxed);
+        }
+
+        let state = self.path.last().unwrap();
+        let pruned = self.problem.bound(state) <= self.best.load(Ordering::Relaxed)
+            || self.problem.key(state).is_some_and(|key| match self.memo.get(&key) {
+                Some(&seen) if seen >= score => true,
+                _ => {
+                    self.memo.insert(key, score);
+                    false
+                }
+            });
+
+        if !pruned {
+            for next in self.problem.moves(state) {
+                self.visit(next);
+            }
+        }
+
+        self.path.pop();
+    }
+}
@@ -0,0 +1,273 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    thread,
+    time::Duration,
+};
+
+use anyhow::Result;
+#[cfg(feature = "gif")]
+use anyhow::Context;
+#[cfg(feature = "gif")]
+use gif::{Encoder, Frame, Repeat};
+#[cfg(feature = "gif")]
+use std::borrow::Cow;
+
+use crate::Options;
+
+pub type Color = [u8; 3];
+
+/// Anything that can be drawn as a grid of cells.
+/// Both years define this trait and [Color] the same way, so a day can be ported between them.
+pub trait Render {
+    /// Width and height in cells
+    fn size(&self) -> (usize, usize);
+
+    /// Index into the palette for the cell at the given position
+    fn cell(&self, x: usize, y: usize) -> u8;
+
+    /// Colours used by the cells, indexed by the value returned from [Render::cell]
+    fn palette(&self) -> &[Color];
+}
+
+/// A single rendered frame, every cell is already scaled up to pixels
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+    pub palette: Vec<Color>,
+}
+
+impl Image {
+    fn new(render: &impl Render, scale: usize) -> Self {
+        let (width, height) = render.size();
+        let (width, height) = (width * scale, height * scale);
+
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                pixels.push(render.cell(x / scale, y / scale));
+            }
+        }
+
+        Self {
+            width,
+            height,
+            pixels,
+            palette: render.palette().to_vec(),
+        }
+    }
+
+    fn color(&self, x: usize, y: usize) -> Color {
+        self.palette[self.pixels[y * self.width + x] as usize]
+    }
+}
+
+/// Destination for rendered frames
+pub trait Sink {
+    /// Delay is in hundredths of a second
+    fn write(&mut self, image: &Image, delay: u16) -> Result<()>;
+
+    /// Sinks that draw every cell as a single character should not be scaled up
+    fn scalable(&self) -> bool {
+        true
+    }
+}
+
+/// Writes all frames to an animated GIF
+#[cfg(feature = "gif")]
+pub struct GifSink {
+    path: PathBuf,
+    encoder: Option<Encoder<BufWriter<File>>>,
+}
+
+#[cfg(feature = "gif")]
+impl GifSink {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into(), encoder: None }
+    }
+}
+
+#[cfg(feature = "gif")]
+impl Sink for GifSink {
+    fn write(&mut self, image: &Image, delay: u16) -> Result<()> {
+        let width = u16::try_from(image.width).context("Frame is too wide for a GIF")?;
+        let height = u16::try_from(image.height).context("Frame is too high for a GIF")?;
+
+        let encoder = match self.encoder.as_mut() {
+            Some(encoder) => encoder,
+            None => {
+                if let Some(parent) = self.path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                let file = BufWriter::new(File::create(&self.path)?);
+                let palette = image.palette.concat();
+                let mut encoder = Encoder::new(file, width, height, &palette)?;
+                encoder.set_repeat(Repeat::Infinite)?;
+                self.encoder.insert(encoder)
+            }
+        };
+
+        let frame = Frame {
+            width,
+            height,
+            buffer: Cow::from(image.pixels.as_slice()),
+            delay,
+            ..Default::default()
+        };
+        encoder.write_frame(&frame)?;
+
+        Ok(())
+    }
+}
+
+/// Writes every frame to a separate numbered PPM file in a directory
+pub struct PpmSink {
+    directory: PathBuf,
+    count: usize,
+}
+
+impl PpmSink {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self { directory: directory.into(), count: 0 }
+    }
+}
+
+impl Sink for PpmSink {
+    fn write(&mut self, image: &Image, _delay: u16) -> Result<()> {
+        fs::create_dir_all(&self.directory)?;
+        let path = self.directory.join(format!("{:05}.ppm", self.count));
+        let mut file = BufWriter::new(File::create(path)?);
+
+        write!(file, "P6\n{} {}\n255\n", image.width, image.height)?;
+        for y in 0..image.height {
+            for x in 0..image.width {
+                file.write_all(&image.color(x, y))?;
+            }
+        }
+
+        self.count += 1;
+        Ok(())
+    }
+}
+
+/// Draws every frame in place in the terminal using ANSI colours
+pub struct AnsiSink<W: Write> {
+    out: W,
+    first: bool,
+    // Overrides the delay of individual frames when set
+    frame_time: Option<Duration>,
+}
+
+impl AnsiSink<io::Stdout> {
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl<W: Write> AnsiSink<W> {
+    pub fn new(out: W) -> Self {
+        Self { out, first: true, frame_time: None }
+    }
+
+    /// Redraw at a fixed frame rate instead of using the delay of every frame
+    pub fn fps(mut self, fps: u32) -> Self {
+        self.frame_time = Some(Duration::from_secs(1) / fps.max(1));
+        self
+    }
+}
+
+impl<W: Write> Sink for AnsiSink<W> {
+    fn write(&mut self, image: &Image, delay: u16) -> Result<()> {
+        let mut buffer = String::new();
+
+        // Clear the screen once, after that only move the cursor back to the top
+        if self.first {
+            buffer += "\x1b[2J";
+            self.first = false;
+        }
+        buffer += "\x1b[H";
+
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let [r, g, b] = image.color(x, y);
+                // Two characters per pixel to keep them roughly square
+                buffer += &format!("\x1b[48;2;{r};{g};{b}m  ");
+            }
+            buffer += "\x1b[0m\n";
+        }
+
+        self.out.write_all(buffer.as_bytes())?;
+        self.out.flush()?;
+        thread::sleep(self.frame_time.unwrap_or(Duration::from_millis(delay as u64 * 10)));
+
+        Ok(())
+    }
+
+    fn scalable(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    Gif,
+    Ppm,
+    Ansi,
+}
+
+/// Renders frames and passes them on to a sink
+pub struct Visualizer {
+    sink: Box<dyn Sink>,
+    scale: usize,
+    delay: u16,
+}
+
+impl Visualizer {
+    pub fn new(sink: Box<dyn Sink>) -> Self {
+        Self { sink, scale: 1, delay: 1 }
+    }
+
+    /// Create a visualizer if it was requested in the options given to the runner.
+    /// Files are written to visualize/<day>/part<part>
+    pub fn from_options(day: u8, part: u8) -> Option<Self> {
+        let options = Options::get();
+        let path = PathBuf::from(format!("visualize/{day}/part{part}"));
+
+        let sink: Box<dyn Sink> = match options.visualize? {
+            #[cfg(feature = "gif")]
+            Format::Gif => Box::new(GifSink::new(path.with_extension("gif"))),
+            #[cfg(not(feature = "gif"))]
+            Format::Gif => unreachable!("GIF output is rejected by the options without the gif feature"),
+            Format::Ppm => Box::new(PpmSink::new(path)),
+            Format::Ansi => match options.fps {
+                Some(fps) => Box::new(AnsiSink::stdout().fps(fps)),
+                None => Box::new(AnsiSink::stdout()),
+            },
+        };
+
+        Some(Self::new(sink))
+    }
+
+    /// Size in pixels of every cell
+    pub fn scale(mut self, scale: usize) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Default delay between frames in hundredths of a second
+    pub fn delay(mut self, delay: u16) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn frame(&mut self, render: &impl Render) -> Result<()> {
+        self.frame_with_delay(render, self.delay)
+    }
+
+    pub fn frame_with_delay(&mut self, render: &impl Render, delay: u16) -> Result<()> {
+        let scale = if self.sink.scalable() { self.scale } else { 1 };
+        self.sink.write(&Image::new(render, scale), delay)
+    }
+}
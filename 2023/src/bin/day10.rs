@@ -1,13 +1,37 @@
 #![feature(test)]
-use core::panic;
-use std::collections::{HashMap, VecDeque};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    str::FromStr,
+};
 
-use anyhow::Result;
-use aoc::Solver;
+use anyhow::{anyhow, Context, Result};
+use aoc::{polygon::Polygon, Output, Solver};
 
 // -- Runners --
 fn main() -> Result<()> {
-    Day::solve()
+    Day::solve_with(&[
+        // Draw the loop and the enclosed tiles
+        Output {
+            name: "render",
+            run: |input, _| {
+                let pipes = Pipes::parse(input);
+                let start = format!(
+                    "Start {:?} is {:?}\n",
+                    pipes.start, pipes.tiles[&pipes.start]
+                );
+                Ok(pipes.render() + &start)
+            },
+        },
+        // Count the enclosed tiles using a specific method
+        Output {
+            name: "mode",
+            run: |input, mode| {
+                let mode: Mode = mode.context("Missing mode, use --mode=<mode>")?.parse()?;
+                let enclosed = Pipes::parse(input).enclosed(mode);
+                Ok(format!("Enclosed ({mode:?}): {enclosed}\n"))
+            },
+        },
+    ])
 }
 
 #[cfg(test)]
@@ -64,6 +88,57 @@ mod tests {
         Day::test(Day::part2, "input", 351)
     }
 
+    #[test]
+    fn modes_agree() -> Result<()> {
+        for name in [
+            "test-1", "test-2", "test-3", "test-4", "test-5", "test-6", "input",
+        ] {
+            Day::test(
+                |input| {
+                    let pipes = Pipes::parse(input);
+                    let counts = [Mode::Shoelace, Mode::Scanline].map(|mode| pipes.enclosed(mode));
+                    counts == [pipes.enclosed(Mode::FloodFill); 2]
+                },
+                name,
+                true,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn start_shape() -> Result<()> {
+        Day::test(
+            |input| {
+                let pipes = Pipes::parse(input);
+                pipes.tiles[&pipes.start]
+            },
+            "test-2",
+            Maze::SouthEast,
+        )
+    }
+
+    #[test]
+    fn render_test3() -> Result<()> {
+        Day::test(
+            |input| Pipes::parse(input).render(),
+            "test-3",
+            "\
+OOOOOOOOOOO
+O┌───────┐O
+O│┌─────┐│O
+O││OOOOO││O
+O││OOOOO││O
+O│└─┐O┌─┘│O
+O│II│O│II│O
+O└──┘O└──┘O
+OOOOOOOOOOO
+"
+            .to_owned(),
+        )
+    }
+
     // Benchmarks
     extern crate test;
     #[bench]
@@ -151,28 +226,24 @@ impl Maze {
                 Maze::Vertical => (1, 1),
                 Maze::NorthEast => (2, 0),
                 Maze::NorthWest => (0, 2),
-                Maze::Start => (0, 0),
                 _ => unreachable!("Invalid movement"),
             },
             (1, 0) => match self {
                 Maze::Horizontal => (1, 1),
                 Maze::NorthEast => (0, 2),
                 Maze::SouthEast => (2, 0),
-                Maze::Start => (0, 0),
                 _ => unreachable!("Invalid movement"),
             },
             (0, 1) => match self {
                 Maze::Vertical => (1, 1),
                 Maze::SouthWest => (2, 0),
                 Maze::SouthEast => (0, 2),
-                Maze::Start => (0, 0),
                 _ => unreachable!("Invalid movement"),
             },
             (-1, 0) => match self {
                 Maze::Horizontal => (1, 1),
                 Maze::NorthWest => (2, 0),
                 Maze::SouthWest => (0, 2),
-                Maze::Start => (0, 0),
                 _ => unreachable!("Invalid movement"),
             },
             _ => unreachable!("Invalid direction"),
@@ -199,16 +270,6 @@ impl Loop {
             _ => unreachable!("Invalid direction"),
         }
     }
-
-    fn offset(&self) -> (isize, isize) {
-        match self {
-            Loop::North => (0, -1),
-            Loop::East => (1, 0),
-            Loop::South => (0, 1),
-            Loop::West => (-1, 0),
-            Loop::Inner => (0, 0),
-        }
-    }
 }
 
 fn flood_fill(position: (isize, isize), map: &mut HashMap<(isize, isize), Loop>) {
@@ -228,167 +289,163 @@ fn flood_fill(position: (isize, isize), map: &mut HashMap<(isize, isize), Loop>)
 
 static DIRECTIONS: [(isize, isize); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
 
-// -- Solution --
-pub struct Day;
-impl aoc::Solver for Day {
-    type Output1 = usize;
-    type Output2 = usize;
+// Ways to count the tiles enclosed by the loop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    FloodFill,
+    // Shoelace formula for the area of the loop, Pick's theorem turns that into the number of tiles
+    Shoelace,
+    // Cross every row from left to right, the tile is inside after crossing the loop an odd number of times
+    Scanline,
+}
 
-    fn day() -> u8 {
-        10
+impl FromStr for Mode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "flood" => Ok(Mode::FloodFill),
+            "shoelace" => Ok(Mode::Shoelace),
+            "scanline" => Ok(Mode::Scanline),
+            _ => Err(anyhow!(
+                "Unknown mode '{s}', expected flood, shoelace or scanline"
+            )),
+        }
     }
+}
 
-    fn part1(input: &str) -> Self::Output1 {
-        let mut position_start = (-1, -1);
+// The pipes with the start replaced by the actual pipe and the main loop in order
+struct Pipes {
+    tiles: HashMap<(isize, isize), Maze>,
+    size: (isize, isize),
+    start: (isize, isize),
+    path: Vec<(isize, isize)>,
+}
 
-        let maze: HashMap<(isize, isize), Maze> = input
+impl Pipes {
+    fn parse(input: &str) -> Self {
+        let mut tiles: HashMap<(isize, isize), Maze> = input
             .lines()
             .enumerate()
             .flat_map(|(y, line)| {
-                line.chars()
-                    .enumerate()
-                    .filter_map(|(x, c)| Maze::from(c).map(|maze| ((x as isize, y as isize), maze)))
-                    .inspect(|(position, maze)| {
-                        if *maze == Maze::Start {
-                            position_start = *position
-                        }
-                    })
-                    .collect::<Vec<_>>()
+                line.chars().enumerate().filter_map(move |(x, c)| {
+                    Maze::from(c).map(|maze| ((x as isize, y as isize), maze))
+                })
             })
             .collect();
 
-        if position_start == (-1, -1) {
-            panic!("No valid start in input");
+        let size = (
+            input.lines().map(|line| line.len()).max().unwrap_or(0) as isize,
+            input.lines().count() as isize,
+        );
+
+        let start = *tiles
+            .iter()
+            .find(|(_, maze)| **maze == Maze::Start)
+            .expect("No valid start in input")
+            .0;
+
+        let (shape, path) =
+            Self::deduce_start(&tiles, start).expect("The start is not part of a loop");
+        tiles.insert(start, shape);
+
+        Self {
+            tiles,
+            size,
+            start,
+            path,
         }
+    }
 
-        let mut position_current = position_start;
-        let mut position_previous = position_start;
-        let mut length = 0;
-        'outer: loop {
-            let tile_current = maze.get(&position_current).unwrap();
-
-            for direction in DIRECTIONS {
-                let position_next = (
-                    position_current.0 + direction.0,
-                    position_current.1 + direction.1,
-                );
-
-                // We are now allowed to go back to the previous position
-                if position_next == position_previous {
-                    continue;
-                }
-
-                if let Some(tile_next) = maze.get(&position_next) {
-                    if tile_current.connects(tile_next, direction) {
-                        position_previous = position_current;
-                        position_current = position_next;
-                        length += 1;
+    // Try every pipe that fits with the neighbours of the start until one of them forms a loop
+    fn deduce_start(
+        tiles: &HashMap<(isize, isize), Maze>,
+        start: (isize, isize),
+    ) -> Option<(Maze, Vec<(isize, isize)>)> {
+        let candidates = [
+            Maze::Vertical,
+            Maze::Horizontal,
+            Maze::NorthEast,
+            Maze::NorthWest,
+            Maze::SouthWest,
+            Maze::SouthEast,
+        ];
+
+        candidates
+            .into_iter()
+            .find_map(|shape| Self::trace(tiles, start, shape).map(|path| (shape, path)))
+    }
 
-                        if position_current == position_start {
-                            return length / 2;
-                        }
+    // Follow the pipes from the start, which is treated as the given shape, until we get back.
+    // None if the pipes do not form a loop.
+    fn trace(
+        tiles: &HashMap<(isize, isize), Maze>,
+        start: (isize, isize),
+        shape: Maze,
+    ) -> Option<Vec<(isize, isize)>> {
+        let tile = |position| {
+            if position == start {
+                Some(shape)
+            } else {
+                tiles.get(&position).copied()
+            }
+        };
 
-                        continue 'outer;
-                    }
-                }
+        let mut path = vec![start];
+        let mut previous = start;
+        let mut current = start;
+        loop {
+            let tile_current = tile(current)?;
+            let next = DIRECTIONS.iter().find_map(|direction| {
+                let next = (current.0 + direction.0, current.1 + direction.1);
+                let connects =
+                    tile(next).is_some_and(|other| tile_current.connects(&other, *direction));
+                (next != previous && connects).then_some(next)
+            })?;
+
+            if next == start {
+                return Some(path);
             }
 
-            unreachable!("Unable to move forward...");
+            path.push(next);
+            previous = current;
+            current = next;
         }
     }
 
-    // Alternate solution that works for the examples and is off-by-one for the actual input
-    fn part2(input: &str) -> Self::Output2 {
-        let mut position_start = (-1, -1);
-        let end_direction;
-        let mut maze: HashMap<(isize, isize), Maze> = input
-            .lines()
-            .enumerate()
-            .flat_map(|(y, line)| {
-                line.chars()
-                    .enumerate()
-                    .filter_map(|(x, c)| Maze::from(c).map(|maze| ((x as isize, y as isize), maze)))
-                    .inspect(|(position, maze)| {
-                        if *maze == Maze::Start {
-                            position_start = *position
-                        }
-                    })
-                    .collect::<Vec<_>>()
-            })
-            .collect();
-
-        let mut map = HashMap::new();
-
-        if position_start == (-1, -1) {
-            panic!("No valid start in input");
+    // Number of tiles enclosed by the loop
+    fn enclosed(&self, mode: Mode) -> usize {
+        match mode {
+            Mode::FloodFill => self.flood_fill(),
+            Mode::Shoelace => {
+                let vertices = self.path.iter().map(|&(x, y)| (x as i128, y as i128));
+                Polygon::from_vertices(vertices).interior_points() as usize
+            }
+            Mode::Scanline => self.inside().len(),
         }
+    }
 
+    // Walk along the loop and flood fill from the tiles on the inside of every pipe
+    fn flood_fill(&self) -> usize {
+        // Direction in which the loop leaves every tile
+        let mut map = HashMap::new();
         let mut sides = (0, 0);
-
-        let mut position_current = position_start;
-        let mut position_previous = position_start;
-        'outer: loop {
-            let tile_current = maze.get(&position_current).unwrap();
-
-            for direction in DIRECTIONS {
-                let position_next = (
-                    position_current.0 + direction.0,
-                    position_current.1 + direction.1,
-                );
-
-                // We are now allowed to go back to the previous position
-                if position_next == position_previous {
-                    continue;
-                }
-
-                if let Some(tile_next) = maze.get(&position_next) {
-                    if tile_current.connects(tile_next, direction) {
-                        map.insert(position_current, Loop::from(direction));
-
-                        position_previous = position_current;
-                        position_current = position_next;
-
-                        let s = tile_current.sides(direction);
-                        sides.0 += s.0;
-                        sides.1 += s.1;
-
-                        if position_current == position_start {
-                            end_direction = Loop::from(direction);
-                            break 'outer;
-                        }
-
-                        continue 'outer;
-                    }
-                }
-            }
-
-            unreachable!("Unable to move forward...");
+        for (i, &position) in self.path.iter().enumerate() {
+            let next = self.path[(i + 1) % self.path.len()];
+            let direction = (next.0 - position.0, next.1 - position.1);
+            map.insert(position, Loop::from(direction));
+
+            let s = self.tiles[&position].sides(direction);
+            sides.0 += s.0;
+            sides.1 += s.1;
         }
 
-        // Replace the start with the correct piece
-        let start = match (end_direction, map.get(&position_start).unwrap()) {
-            (Loop::North, Loop::North) | (Loop::South, Loop::South) => Maze::Vertical,
-            (Loop::East, Loop::East) | (Loop::West, Loop::West) => Maze::Horizontal,
-            (Loop::South, Loop::East) | (Loop::West, Loop::North) => Maze::NorthEast,
-            (Loop::South, Loop::West) | (Loop::East, Loop::North) => Maze::NorthWest,
-            (Loop::East, Loop::South) | (Loop::North, Loop::West) => Maze::SouthWest,
-            (Loop::North, Loop::East) | (Loop::West, Loop::South) => Maze::SouthEast,
-            _ => unreachable!(),
-        };
-        maze.insert(position_start, start);
-
-        // Add the sides of the start
-        let start_sides = start.sides(map.get(&position_start).unwrap().offset());
-        sides.0 += start_sides.0;
-        sides.1 += start_sides.1;
-
         // Determine if the loop was travelled clockwise or anti clockwise
         let clockwise = sides.0 > sides.1;
 
-        let mut position_current = position_start;
-        loop {
-            let &direction = map.get(&position_current).unwrap();
-            let tile = maze.get(&position_current).unwrap();
+        for &position_current in &self.path {
+            let direction = map[&position_current];
+            let tile = self.tiles[&position_current];
 
             // Get neighbouring tiles that are inside of the loop
             let checks = match direction {
@@ -496,15 +553,6 @@ impl aoc::Solver for Day {
                 let check = (position_current.0 + check.0, position_current.1 + check.1);
                 flood_fill(check, &mut map);
             }
-
-            let direction = direction.offset();
-
-            position_current.0 += direction.0;
-            position_current.1 += direction.1;
-
-            if position_current == position_start {
-                break;
-            }
         }
 
         // Count how many tiles are marked as inner
@@ -512,4 +560,81 @@ impl aoc::Solver for Day {
             .filter(|(_, &value)| value == Loop::Inner)
             .count()
     }
+
+    // Tiles inside the loop, using the scanline method
+    fn inside(&self) -> HashSet<(isize, isize)> {
+        let on_loop: HashSet<_> = self.path.iter().copied().collect();
+
+        let mut inside = HashSet::new();
+        for y in 0..self.size.1 {
+            let mut crossings = 0;
+            for x in 0..self.size.0 {
+                if on_loop.contains(&(x, y)) {
+                    // Only count pipes going north, so a horizontal run is crossed exactly once when it
+                    // ends up on the other side
+                    if matches!(
+                        self.tiles[&(x, y)],
+                        Maze::Vertical | Maze::NorthEast | Maze::NorthWest
+                    ) {
+                        crossings += 1;
+                    }
+                } else if crossings % 2 == 1 {
+                    inside.insert((x, y));
+                }
+            }
+        }
+
+        inside
+    }
+
+    // Draw the loop using box drawing characters, the other tiles are marked as inside (I) or
+    // outside (O)
+    fn render(&self) -> String {
+        let on_loop: HashSet<_> = self.path.iter().copied().collect();
+        let inside = self.inside();
+
+        let mut output = String::new();
+        for y in 0..self.size.1 {
+            for x in 0..self.size.0 {
+                let c = if on_loop.contains(&(x, y)) {
+                    match self.tiles[&(x, y)] {
+                        Maze::Vertical => '│',
+                        Maze::Horizontal => '─',
+                        Maze::NorthEast => '└',
+                        Maze::NorthWest => '┘',
+                        Maze::SouthWest => '┐',
+                        Maze::SouthEast => '┌',
+                        Maze::Start => unreachable!("The start has been replaced"),
+                    }
+                } else if inside.contains(&(x, y)) {
+                    'I'
+                } else {
+                    'O'
+                };
+                output.push(c);
+            }
+            output.push('\n');
+        }
+
+        output
+    }
+}
+
+// -- Solution --
+pub struct Day;
+impl aoc::Solver for Day {
+    type Output1 = usize;
+    type Output2 = usize;
+
+    fn day() -> u8 {
+        10
+    }
+
+    fn part1(input: &str) -> Self::Output1 {
+        Pipes::parse(input).path.len() / 2
+    }
+
+    fn part2(input: &str) -> Self::Output2 {
+        Pipes::parse(input).enclosed(Mode::FloodFill)
+    }
 }
@@ -31,6 +31,48 @@ mod tests {
         Day::test(Day::part2, "input", 35799)
     }
 
+    #[test]
+    fn smudges_test1() -> Result<()> {
+        Day::test(
+            |input| {
+                input
+                    .split("\n\n")
+                    .flat_map(|block| Pattern::parse(block).reflections(1))
+                    .collect::<Vec<_>>()
+            },
+            "test-1",
+            vec![
+                Reflection {
+                    orientation: Orientation::Horizontal,
+                    position: 3,
+                    smudges: vec![((0, 0), (0, 5))],
+                },
+                Reflection {
+                    orientation: Orientation::Horizontal,
+                    position: 1,
+                    smudges: vec![((4, 0), (4, 1))],
+                },
+            ],
+        )
+    }
+
+    #[test]
+    fn multiple_smudges() {
+        let pattern = Pattern::parse(
+            "#.##..##.\n..#.##.#.\n##......#\n##......#\n..#.##.#.\n..##..##.\n#.#.##.#.",
+        );
+        assert_eq!(pattern.summarize(0), 5);
+
+        assert_eq!(
+            pattern.reflections(2),
+            vec![Reflection {
+                orientation: Orientation::Vertical,
+                position: 1,
+                smudges: vec![((0, 0), (1, 0)), ((0, 6), (1, 6))],
+            }]
+        );
+    }
+
     // Benchmarks
     extern crate test;
     #[bench]
@@ -51,15 +93,6 @@ enum Ground {
     Rocks,
 }
 
-impl Ground {
-    fn flip(&self) -> Self {
-        match self {
-            Ground::Ash => Ground::Rocks,
-            Ground::Rocks => Ground::Ash,
-        }
-    }
-}
-
 impl From<char> for Ground {
     fn from(c: char) -> Self {
         match c {
@@ -70,6 +103,139 @@ impl From<char> for Ground {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Orientation {
+    // Mirror line between two rows
+    Horizontal,
+    // Mirror line between two columns
+    Vertical,
+}
+
+// A cell as (x, y) together with the cell it is mirrored onto, flipping either one of them
+// removes the smudge
+type Smudge = ((usize, usize), (usize, usize));
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Reflection {
+    orientation: Orientation,
+    // Number of rows above or columns left of the mirror line
+    position: usize,
+    smudges: Vec<Smudge>,
+}
+
+impl Reflection {
+    fn summarize(&self) -> usize {
+        match self.orientation {
+            Orientation::Horizontal => 100 * self.position,
+            Orientation::Vertical => self.position,
+        }
+    }
+}
+
+// Every row and every column is stored as a bitmask where rocks are set
+#[derive(Debug, Clone)]
+struct Pattern {
+    rows: Vec<u64>,
+    columns: Vec<u64>,
+}
+
+impl Pattern {
+    fn parse(block: &str) -> Self {
+        let grid: Vec<Vec<Ground>> = block
+            .lines()
+            .map(|line| line.chars().map(|c| c.into()).collect())
+            .collect();
+        let width = grid[0].len();
+        assert!(
+            width <= 64 && grid.len() <= 64,
+            "Patterns can be at most 64 by 64"
+        );
+
+        let mut rows = vec![0; grid.len()];
+        let mut columns = vec![0; width];
+        for (y, line) in grid.iter().enumerate() {
+            for (x, ground) in line.iter().enumerate() {
+                if *ground == Ground::Rocks {
+                    rows[y] |= 1 << x;
+                    columns[x] |= 1 << y;
+                }
+            }
+        }
+
+        Self { rows, columns }
+    }
+
+    // Find all mirror lines where exactly the given number of cells differ from their reflection
+    fn reflections(&self, smudges: usize) -> Vec<Reflection> {
+        let horizontal = mirror_lines(&self.rows, smudges)
+            .into_iter()
+            .map(|(position, cells)| Reflection {
+                orientation: Orientation::Horizontal,
+                position,
+                smudges: cells,
+            });
+
+        // For columns the line index is x and the bit is y, so swap them back
+        let vertical = mirror_lines(&self.columns, smudges)
+            .into_iter()
+            .map(|(position, cells)| Reflection {
+                orientation: Orientation::Vertical,
+                position,
+                smudges: cells
+                    .into_iter()
+                    .map(|((y, x), (my, mx))| ((x, y), (mx, my)))
+                    .collect(),
+            });
+
+        horizontal.chain(vertical).collect()
+    }
+
+    // The single mirror line with the given number of smudges
+    fn summarize(&self, smudges: usize) -> usize {
+        match self.reflections(smudges).as_slice() {
+            [reflection] => reflection.summarize(),
+            reflections => unreachable!(
+                "Expected exactly one mirror line, found {}",
+                reflections.len()
+            ),
+        }
+    }
+}
+
+// Check every line between two entries, the number of differing cells is the popcount of the xor
+// of every mirrored pair. The cells are returned as (bit, line).
+fn mirror_lines(lines: &[u64], smudges: usize) -> Vec<(usize, Vec<Smudge>)> {
+    (1..lines.len())
+        .filter_map(|position| {
+            let pairs = || (0..position).rev().zip(position..lines.len());
+
+            let mut differences = 0;
+            for (a, b) in pairs() {
+                differences += (lines[a] ^ lines[b]).count_ones() as usize;
+                if differences > smudges {
+                    return None;
+                }
+            }
+
+            if differences != smudges {
+                return None;
+            }
+
+            let mut cells = Vec::new();
+            for (a, b) in pairs() {
+                let mut diff = lines[a] ^ lines[b];
+                while diff != 0 {
+                    let bit = diff.trailing_zeros() as usize;
+                    cells.push(((bit, a), (bit, b)));
+                    diff &= diff - 1;
+                }
+            }
+
+            Some((position, cells))
+        })
+        .collect()
+}
+
 // -- Solution --
 pub struct Day;
 impl aoc::Solver for Day {
@@ -83,115 +249,14 @@ impl aoc::Solver for Day {
     fn part1(input: &str) -> Self::Output1 {
         input
             .split("\n\n")
-            .map(|block| {
-                block
-                    .lines()
-                    .map(|line| line.chars().map(|c| c.into()).collect::<Vec<Ground>>())
-                    .collect::<Vec<Vec<_>>>()
-            })
-            .map(|block| {
-                let height = block.len();
-                let width = block[0].len();
-
-                if let Some(y) = (1..height).find(|&y| {
-                    let top = block[0..y].iter().rev();
-                    let bottom = block[y..height].iter();
-
-                    top.zip(bottom).all(|(a, b)| a == b)
-                }) {
-                    100 * y
-                } else if let Some(x) = (1..width).find(|&x| {
-                    block.iter().all(|line| {
-                        let left = line[0..x].iter().rev();
-                        let right = line[x..width].iter();
-
-                        left.zip(right).all(|(a, b)| a == b)
-                    })
-                }) {
-                    x
-                } else {
-                    unreachable!("Their is always a mirror line")
-                }
-            })
+            .map(|block| Pattern::parse(block).summarize(0))
             .sum()
     }
 
     fn part2(input: &str) -> Self::Output2 {
         input
             .split("\n\n")
-            .map(|block| {
-                block
-                    .lines()
-                    .map(|line| line.chars().map(|c| c.into()).collect::<Vec<Ground>>())
-                    .collect::<Vec<Vec<_>>>()
-            })
-            .map(|mut block| {
-                let height = block.len();
-                let width = block[0].len();
-
-                // Get the row/column of the old mirror line
-                let (x_old, y_old) = if let Some(y) = (1..height).find(|&y| {
-                    let top = block[0..y].iter().rev();
-                    let bottom = block[y..height].iter();
-
-                    top.zip(bottom).all(|(a, b)| a == b)
-                }) {
-                    (None, Some(y))
-                } else if let Some(x) = (1..width).find(|&x| {
-                    block.iter().all(|line| {
-                        let left = line[0..x].iter().rev();
-                        let right = line[x..width].iter();
-
-                        left.zip(right).all(|(a, b)| a == b)
-                    })
-                }) {
-                    (Some(x), None)
-                } else {
-                    unreachable!("Their is always a mirror line")
-                };
-
-                // Try every possible smudge location
-                for j in 0..width {
-                    for i in 0..height {
-                        // Flip one
-                        block[i][j] = block[i][j].flip();
-
-                        if let Some(y) = (1..height).find(|&y| {
-                            // The mirror line can not be the old one
-                            if y_old == Some(y) {
-                                return false;
-                            }
-
-                            let top = block[0..y].iter().rev();
-                            let bottom = block[y..height].iter();
-
-                            top.zip(bottom).all(|(a, b)| a == b)
-                        }) {
-                            return 100 * y;
-                        } else if let Some(x) = (1..width).find(|&x| {
-                            // The mirror line can not be the old one
-                            if x_old == Some(x) {
-                                return false;
-                            }
-
-                            block.iter().all(|line| {
-                                let left = line[0..x].iter().rev();
-                                let right = line[x..width].iter();
-
-                                left.zip(right).all(|(a, b)| a == b)
-                            })
-                        }) {
-                            return x;
-                        } else {
-                            // Revert flip
-                            block[i][j] = block[i][j].flip();
-                            continue;
-                        }
-                    }
-                }
-
-                unreachable!("Their should always be a mirror line")
-            })
+            .map(|block| Pattern::parse(block).summarize(1))
             .sum()
     }
 }
@@ -1,13 +1,12 @@
 #![feature(test)]
 
-use std::{
-    collections::{hash_map::DefaultHasher, HashMap},
-    fmt::Display,
-    hash::{Hash, Hasher},
-};
+use std::{collections::HashMap, fmt::Display, io::Stdout};
 
 use anyhow::Result;
-use aoc::Solver;
+use aoc::{
+    visualize::{Color, Render, Terminal},
+    Solver,
+};
 
 // -- Runners --
 fn main() -> Result<()> {
@@ -38,6 +37,55 @@ mod tests {
         Day::test(Day::part2, "input", 87273)
     }
 
+    #[test]
+    fn tilt_directions() {
+        let mut board = Board::parse("O.#.O\n.O..O\n..O.#");
+        board.tilt(Direction::East);
+        assert_eq!(board.to_string(), ".O#.O\n...OO\n...O#\n");
+        board.tilt(Direction::South);
+        assert_eq!(board.to_string(), "..#.O\n...OO\n.O.O#\n");
+        board.tilt(Direction::West);
+        assert_eq!(board.to_string(), "..#O.\nOO...\nOO..#\n");
+        board.tilt(Direction::North);
+        assert_eq!(board.to_string(), "OO#O.\nOO...\n....#\n");
+    }
+
+    #[test]
+    fn board_widths() {
+        let mut empty = Board::parse("\n\n");
+        empty.tilt(Direction::East);
+        assert_eq!(empty.load(), 0);
+
+        let mut wide = Board::parse(&("O".to_owned() + &".".repeat(127)));
+        wide.tilt(Direction::East);
+        assert_eq!(wide.to_string(), ".".repeat(127) + "O\n");
+    }
+
+    #[test]
+    fn cycles_test1() -> Result<()> {
+        Day::test(
+            |input| {
+                let mut board = Board::parse(input);
+                board.cycle(&mut None);
+                board.to_string()
+            },
+            "test-1",
+            "\
+.....#....
+....#...O#
+...OO##...
+.OO#......
+.....OOO#.
+.O#...O#.#
+....O#....
+......OOOO
+#...O###..
+#..OO#....
+"
+            .to_owned(),
+        )
+    }
+
     // Benchmarks
     extern crate test;
     #[bench]
@@ -52,160 +100,155 @@ mod tests {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
-enum Space {
-    Empty,
-    Cube,
-    Round,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    North,
+    East,
+    South,
+    West,
 }
 
-impl Display for Space {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl Direction {
+    fn opposite(&self) -> Self {
         match self {
-            Space::Empty => write!(f, "."),
-            Space::Cube => write!(f, "#"),
-            Space::Round => write!(f, "O"),
+            Direction::North => Direction::South,
+            Direction::East => Direction::West,
+            Direction::South => Direction::North,
+            Direction::West => Direction::East,
         }
     }
 }
 
-impl From<char> for Space {
-    fn from(c: char) -> Self {
-        match c {
-            '.' => Self::Empty,
-            '#' => Self::Cube,
-            'O' => Self::Round,
-            _ => unreachable!("Invalid input"),
+// Every row is stored as a bitmask, bit x is set if there is a rock in column x
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Board {
+    width: usize,
+    round: Vec<u128>,
+    cube: Vec<u128>,
+}
+
+impl Board {
+    fn parse(input: &str) -> Self {
+        let width = input.lines().map(|line| line.len()).max().unwrap_or(0);
+        assert!(width <= 128, "The platform can be at most 128 wide");
+
+        let row = |line: &str, rock: char| {
+            line.chars()
+                .enumerate()
+                .filter(|(_, c)| *c == rock)
+                .fold(0_u128, |row, (x, _)| row | 1 << x)
+        };
+
+        Self {
+            width,
+            round: input.lines().map(|line| row(line, 'O')).collect(),
+            cube: input.lines().map(|line| row(line, '#')).collect(),
         }
     }
-}
 
-fn print_grid(grid: &[Vec<Space>]) {
-    for line in grid {
-        for space in line {
-            print!("{space}");
+    fn mask(&self) -> u128 {
+        // Shifting by the full width is not allowed, which happens for an empty board
+        u128::MAX.checked_shr(128 - self.width as u32).unwrap_or(0)
+    }
+
+    // Move every cell one step in the given direction, cells that move off the board are dropped
+    fn shift(&self, rows: &[u128], direction: Direction) -> Vec<u128> {
+        let height = rows.len();
+        match direction {
+            Direction::North => (0..height)
+                .map(|y| rows.get(y + 1).copied().unwrap_or(0))
+                .collect(),
+            Direction::South => (0..height)
+                .map(|y| if y == 0 { 0 } else { rows[y - 1] })
+                .collect(),
+            Direction::West => rows.iter().map(|row| row >> 1).collect(),
+            Direction::East => rows.iter().map(|row| (row << 1) & self.mask()).collect(),
         }
-        println!();
     }
-}
 
-fn tilt_north(grid: &mut Vec<Vec<Space>>) {
-    let width = grid[0].len();
-    let height = grid.len();
-
-    for y in 0..height {
-        for x in 0..width {
-            if grid[y][x] == Space::Round {
-                let mut new_y = y;
-                for yy in (0..y).rev() {
-                    if grid[yy][x] == Space::Empty {
-                        new_y = yy
-                    } else {
-                        break;
-                    }
-                }
-                if new_y < y {
-                    grid[y][x] = Space::Empty;
-                    grid[new_y][x] = Space::Round;
-                }
+    // Every round rock that has a free space next to it moves one step, this is repeated until no
+    // rock can move anymore. All rows are handled at the same time.
+    fn tilt(&mut self, direction: Direction) {
+        loop {
+            let moved: Vec<_> = self
+                .shift(&self.round, direction)
+                .iter()
+                .zip(self.round.iter().zip(&self.cube))
+                .map(|(incoming, (round, cube))| incoming & !(round | cube))
+                .collect();
+
+            if moved.iter().all(|row| *row == 0) {
+                break;
+            }
+
+            let left = self.shift(&moved, direction.opposite());
+            for ((round, moved), left) in self.round.iter_mut().zip(moved).zip(left) {
+                *round = (*round & !left) | moved;
             }
         }
     }
-}
 
-fn tilt_east(grid: &mut [Vec<Space>]) {
-    let width = grid[0].len();
-
-    for x in (0..width).rev() {
-        for line in grid.iter_mut() {
-            if line[x] == Space::Round {
-                let mut new_x = x;
-                for (xx, space) in line.iter().enumerate().skip(x + 1) {
-                    if space == &Space::Empty {
-                        new_x = xx
-                    } else {
-                        break;
-                    }
-                }
-                if new_x > x {
-                    line[x] = Space::Empty;
-                    line[new_x] = Space::Round;
-                }
-            }
+    fn cycle(&mut self, terminal: &mut Option<Terminal<Stdout>>) {
+        for direction in [
+            Direction::North,
+            Direction::West,
+            Direction::South,
+            Direction::East,
+        ] {
+            self.tilt(direction);
+            show(terminal, self);
         }
     }
+
+    fn load(&self) -> usize {
+        self.round
+            .iter()
+            .enumerate()
+            .map(|(y, row)| (self.round.len() - y) * row.count_ones() as usize)
+            .sum()
+    }
 }
 
-fn tilt_south(grid: &mut Vec<Vec<Space>>) {
-    let width = grid[0].len();
-    let height = grid.len();
-
-    for y in (0..height).rev() {
-        for x in 0..width {
-            if grid[y][x] == Space::Round {
-                let mut new_y = y;
-                for (yy, line) in grid.iter().enumerate().skip(y + 1) {
-                    if line[x] == Space::Empty {
-                        new_y = yy
-                    } else {
-                        break;
-                    }
-                }
-                if new_y > y {
-                    grid[y][x] = Space::Empty;
-                    grid[new_y][x] = Space::Round;
+impl Display for Board {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (round, cube) in self.round.iter().zip(&self.cube) {
+            for x in 0..self.width {
+                match (round >> x & 1, cube >> x & 1) {
+                    (1, _) => write!(f, "O")?,
+                    (_, 1) => write!(f, "#")?,
+                    _ => write!(f, ".")?,
                 }
             }
+            writeln!(f)?;
         }
+
+        Ok(())
     }
 }
 
-fn tilt_west(grid: &mut [Vec<Space>]) {
-    let width = grid[0].len();
-
-    for x in 0..width {
-        for line in grid.iter_mut() {
-            if line[x] == Space::Round {
-                let mut new_x = x;
-                for xx in (0..x).rev() {
-                    if line[xx] == Space::Empty {
-                        new_x = xx
-                    } else {
-                        break;
-                    }
-                }
+impl Render for Board {
+    fn size(&self) -> (usize, usize) {
+        (self.width, self.round.len())
+    }
 
-                if new_x < x {
-                    line[x] = Space::Empty;
-                    line[new_x] = Space::Round;
-                }
-            }
+    fn cell(&self, x: usize, y: usize) -> u8 {
+        match (self.round[y] >> x & 1, self.cube[y] >> x & 1) {
+            (1, _) => 2,
+            (_, 1) => 1,
+            _ => 0,
         }
     }
-}
 
-fn calculate_load(grid: &Vec<Vec<Space>>) -> usize {
-    grid.iter()
-        .enumerate()
-        .map(|(y, line)| {
-            line.iter()
-                .filter_map(|space| {
-                    if space == &Space::Round {
-                        Some(grid.len() - y)
-                    } else {
-                        None
-                    }
-                })
-                .sum::<usize>()
-        })
-        .sum()
+    fn palette(&self) -> &[Color] {
+        &[[0x20, 0x20, 0x20], [0x80, 0x80, 0x80], [0xFF, 0xC0, 0x40]]
+    }
 }
 
-fn cycle(grid: &mut Vec<Vec<Space>>) {
-    tilt_north(grid);
-    tilt_west(grid);
-    tilt_south(grid);
-    tilt_east(grid);
+// Draw the board if live animation is enabled
+fn show(terminal: &mut Option<Terminal<Stdout>>, board: &Board) {
+    if let Some(terminal) = terminal {
+        terminal.frame(board).unwrap();
+    }
 }
 
 // -- Solution --
@@ -219,47 +262,41 @@ impl aoc::Solver for Day {
     }
 
     fn part1(input: &str) -> Self::Output1 {
-        let mut grid: Vec<Vec<Space>> = input
-            .lines()
-            .map(|line| line.chars().map(|c| c.into()).collect())
-            .collect();
+        let mut board = Board::parse(input);
+
+        let mut terminal = Terminal::from_options();
+        show(&mut terminal, &board);
 
-        tilt_north(&mut grid);
+        board.tilt(Direction::North);
+        show(&mut terminal, &board);
 
-        calculate_load(&grid)
+        board.load()
     }
 
     fn part2(input: &str) -> Self::Output2 {
-        let mut grid: Vec<Vec<Space>> = input
-            .lines()
-            .map(|line| line.chars().map(|c| c.into()).collect())
-            .collect();
+        let mut board = Board::parse(input);
+
+        let mut terminal = Terminal::from_options();
+        show(&mut terminal, &board);
 
-        let mut cache = HashMap::new();
+        // The cube rocks never move, so the round rocks are the entire state
+        let mut seen = HashMap::new();
         let mut cycles = 0;
         let loop_length = loop {
-            cycle(&mut grid);
+            board.cycle(&mut terminal);
             cycles += 1;
 
-            // Calculate the hash of the current state
-            let mut hash = DefaultHasher::new();
-            grid.hash(&mut hash);
-            let hash = hash.finish();
-
             // Check if we have encountered this state before
-            if let Some(c) = cache.get(&hash) {
+            if let Some(c) = seen.insert(board.round.clone(), cycles) {
                 break cycles - c;
             }
-
-            // Insert the state in the map
-            cache.insert(hash, cycles);
         };
 
         let remaining = (1000000000 - cycles) % loop_length;
         for _ in 0..remaining {
-            cycle(&mut grid);
+            board.cycle(&mut terminal);
         }
 
-        calculate_load(&grid)
+        board.load()
     }
 }
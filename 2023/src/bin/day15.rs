@@ -1,12 +1,18 @@
 #![feature(test)]
-use std::cmp::Ordering;
+use std::{collections::HashMap, fmt};
 
 use anyhow::Result;
-use aoc::Solver;
+use aoc::{Output, Solver};
 
 // -- Runners --
 fn main() -> Result<()> {
-    Day::solve()
+    Day::solve_with(&[
+        // Print the boxes after every step instead of the answers
+        Output {
+            name: "trace",
+            run: |input, _| Ok(trace(input)),
+        },
+    ])
 }
 
 #[cfg(test)]
@@ -33,6 +39,89 @@ mod tests {
         Day::test(Day::part2, "input", 290779)
     }
 
+    #[test]
+    fn trace_test1() -> Result<()> {
+        Day::test(
+            trace,
+            "test-1",
+            "\
+After \"rn=1\":
+Box 0: [rn 1]
+
+After \"cm-\":
+Box 0: [rn 1]
+
+After \"qp=3\":
+Box 0: [rn 1]
+Box 1: [qp 3]
+
+After \"cm=2\":
+Box 0: [rn 1] [cm 2]
+Box 1: [qp 3]
+
+After \"qp-\":
+Box 0: [rn 1] [cm 2]
+
+After \"pc=4\":
+Box 0: [rn 1] [cm 2]
+Box 3: [pc 4]
+
+After \"ot=9\":
+Box 0: [rn 1] [cm 2]
+Box 3: [pc 4] [ot 9]
+
+After \"ab=5\":
+Box 0: [rn 1] [cm 2]
+Box 3: [pc 4] [ot 9] [ab 5]
+
+After \"pc-\":
+Box 0: [rn 1] [cm 2]
+Box 3: [ot 9] [ab 5]
+
+After \"pc=6\":
+Box 0: [rn 1] [cm 2]
+Box 3: [ot 9] [ab 5] [pc 6]
+
+After \"ot=7\":
+Box 0: [rn 1] [cm 2]
+Box 3: [ot 7] [ab 5] [pc 6]
+"
+            .to_owned(),
+        )
+    }
+
+    #[test]
+    fn removal_keeps_order() {
+        // Both labels end up in box 0, removing the first one over and over leaves lots of empty
+        // slots behind that have to be compacted
+        let labels = ["rn", "cm"];
+        assert!(labels.iter().all(|label| hash(label) == 0));
+
+        let mut boxes = LensBoxes::new();
+        for focal_length in 1..=20 {
+            boxes.apply(&Action::Add {
+                label: "rn",
+                focal_length,
+            });
+            boxes.apply(&Action::Add {
+                label: "cm",
+                focal_length,
+            });
+            boxes.apply(&Action::Remove { label: "rn" });
+        }
+        boxes.apply(&Action::Add {
+            label: "rn",
+            focal_length: 3,
+        });
+
+        assert_eq!(
+            boxes.lenses(0).collect::<Vec<_>>(),
+            vec![("cm", 20), ("rn", 3)]
+        );
+        assert_eq!(boxes.focusing_power(), 20 + 2 * 3);
+        assert!(boxes.slots[0].len() <= 12);
+    }
+
     // Benchmarks
     extern crate test;
     #[bench]
@@ -47,6 +136,14 @@ mod tests {
     }
 }
 
+// The HASH algorithm from the puzzle
+fn hash(string: &str) -> usize {
+    string
+        .chars()
+        .map(|c| c as usize)
+        .fold(0, |acc, num| ((acc + num) * 17) % 256)
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum Action<'a> {
     Add { label: &'a str, focal_length: usize },
@@ -54,100 +151,106 @@ enum Action<'a> {
 }
 
 impl<'a> Action<'a> {
-    fn index(&self) -> usize {
+    fn parse(step: &'a str) -> Self {
+        if let Some(label) = step.strip_suffix('-') {
+            Action::Remove { label }
+        } else {
+            let (label, focal_length) = step.split_once('=').expect("Invalid input");
+            let focal_length = focal_length.parse().expect("Invalid focal length");
+
+            Action::Add {
+                label,
+                focal_length,
+            }
+        }
+    }
+
+    fn label(&self) -> &'a str {
         match self {
-            Action::Add { label, .. } | Action::Remove { label } => label
-                .chars()
-                .map(|c| c as usize)
-                .fold(0, |acc, num| ((acc + num) * 17) % 256),
+            Action::Add { label, .. } | Action::Remove { label } => label,
         }
     }
 }
 
-// -- Solution --
-pub struct Day;
-impl aoc::Solver for Day {
-    type Output1 = usize;
-    type Output2 = usize;
-
-    fn day() -> u8 {
-        15
+impl fmt::Display for Action<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Add {
+                label,
+                focal_length,
+            } => write!(f, "{label}={focal_length}"),
+            Action::Remove { label } => write!(f, "{label}-"),
+        }
     }
+}
 
-    fn part1(input: &str) -> Self::Output1 {
-        input
-            .trim()
-            .split(',')
-            .map(|sequence| {
-                sequence
-                    .chars()
-                    .map(|c| c as usize)
-                    .fold(0, |acc, num| ((acc + num) * 17) % 256)
-            })
-            .sum()
-    }
+// Every box keeps its lenses in insertion order. Removing a lens only leaves an empty slot behind so
+// it does not have to shift the lenses after it, the slots are compacted once they are mostly empty.
+#[derive(Debug, Clone)]
+struct LensBoxes<'a> {
+    slots: Vec<Vec<Option<(&'a str, usize)>>>,
+    // Number of lenses that are actually in every box
+    counts: Vec<usize>,
+    // Slot of every label that is in a box, the box follows from the label
+    positions: HashMap<&'a str, usize>,
+}
 
-    fn part2(input: &str) -> Self::Output2 {
-        let actions: Vec<_> = input
-            .trim()
-            .split(',')
-            .map(|sequence| {
-                if sequence.ends_with('-') {
-                    let label = sequence.split_once('-').unwrap().0;
-
-                    Action::Remove { label }
-                } else {
-                    let (label, focal_length) = sequence.split_once('=').unwrap();
+impl<'a> LensBoxes<'a> {
+    fn new() -> Self {
+        Self {
+            slots: vec![Vec::new(); 256],
+            counts: vec![0; 256],
+            positions: HashMap::new(),
+        }
+    }
 
-                    let focal_length = focal_length.parse().unwrap();
+    fn apply(&mut self, action: &Action<'a>) {
+        let index = hash(action.label());
 
-                    Action::Add {
-                        label,
-                        focal_length,
-                    }
-                }
-            })
-            .collect();
-
-        let mut boxes: [Vec<(&str, usize)>; 256] = std::array::from_fn(|_| Vec::new());
-
-        for action in &actions {
-            let index = action.index();
-
-            match action {
-                Action::Add {
-                    label,
-                    focal_length,
-                } => {
-                    // NOTE: Using a library like OrderedMap would make this trivially easy
-                    if let Some(position) = boxes[index]
-                        .iter()
-                        .position(|lens| lens.0.cmp(label) == Ordering::Equal)
-                    {
-                        // If a lens with this label is already in the box, replace it
-                        boxes[index][position].1 = *focal_length;
-                    } else {
-                        // Otherwise add it to the end of the box
-                        boxes[index].push((label, *focal_length));
-                    }
+        match *action {
+            Action::Add {
+                label,
+                focal_length,
+            } => {
+                if let Some(&position) = self.positions.get(label) {
+                    // If a lens with this label is already in the box, replace it
+                    self.slots[index][position] = Some((label, focal_length));
+                } else {
+                    // Otherwise add it to the end of the box
+                    self.positions.insert(label, self.slots[index].len());
+                    self.slots[index].push(Some((label, focal_length)));
+                    self.counts[index] += 1;
                 }
-                Action::Remove { label } => {
-                    if let Some(position) = boxes[index]
-                        .iter()
-                        .position(|lens| lens.0.cmp(label) == Ordering::Equal)
-                    {
-                        // A lens with the label exists, remove it
-                        boxes[index].remove(position);
+            }
+            Action::Remove { label } => {
+                if let Some(position) = self.positions.remove(label) {
+                    self.slots[index][position] = None;
+                    self.counts[index] -= 1;
+
+                    if self.slots[index].len() > 2 * self.counts[index] + 8 {
+                        self.compact(index);
                     }
                 }
             }
         }
+    }
+
+    fn compact(&mut self, index: usize) {
+        self.slots[index].retain(Option::is_some);
+        for (position, (label, _)) in self.slots[index].iter().flatten().enumerate() {
+            self.positions.insert(label, position);
+        }
+    }
 
-        boxes
-            .iter()
-            .enumerate()
-            .map(|(index, b)| {
-                b.iter()
+    // Lenses in a box from front to back as (label, focal length)
+    fn lenses(&self, index: usize) -> impl Iterator<Item = (&'a str, usize)> + '_ {
+        self.slots[index].iter().flatten().copied()
+    }
+
+    fn focusing_power(&self) -> usize {
+        (0..self.slots.len())
+            .map(|index| {
+                self.lenses(index)
                     .enumerate()
                     .map(|(position, (_, focal_length))| {
                         (1 + index) * (1 + position) * focal_length
@@ -157,3 +260,63 @@ impl aoc::Solver for Day {
             .sum()
     }
 }
+
+// Same format as the walkthrough in the puzzle, empty boxes are skipped
+impl fmt::Display for LensBoxes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, count) in self.counts.iter().enumerate() {
+            if *count == 0 {
+                continue;
+            }
+
+            write!(f, "Box {index}:")?;
+            for (label, focal_length) in self.lenses(index) {
+                write!(f, " [{label} {focal_length}]")?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn parse(input: &str) -> Vec<Action<'_>> {
+    input.trim().split(',').map(Action::parse).collect()
+}
+
+// Contents of the boxes after every step
+fn trace(input: &str) -> String {
+    let mut boxes = LensBoxes::new();
+    parse(input)
+        .iter()
+        .map(|action| {
+            boxes.apply(action);
+            format!("After \"{action}\":\n{boxes}")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// -- Solution --
+pub struct Day;
+impl aoc::Solver for Day {
+    type Output1 = usize;
+    type Output2 = usize;
+
+    fn day() -> u8 {
+        15
+    }
+
+    fn part1(input: &str) -> Self::Output1 {
+        input.trim().split(',').map(hash).sum()
+    }
+
+    fn part2(input: &str) -> Self::Output2 {
+        let mut boxes = LensBoxes::new();
+        for action in &parse(input) {
+            boxes.apply(action);
+        }
+
+        boxes.focusing_power()
+    }
+}
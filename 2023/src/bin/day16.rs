@@ -2,11 +2,22 @@
 use std::collections::{HashMap, HashSet};
 
 use anyhow::Result;
-use aoc::Solver;
+use aoc::{Output, Solver};
+use petgraph::{algo::tarjan_scc, graphmap::DiGraphMap};
 
 // -- Runners --
 fn main() -> Result<()> {
-    Day::solve()
+    Day::solve_with(&[
+        // Draw the energized tiles for the beam entering in the top left
+        Output {
+            name: "render",
+            run: |input, _| {
+                let contraption = Contraption::parse(input);
+                let energized = Beams::new(&contraption).energized((0, 0), (1, 0));
+                Ok(contraption.render(&energized))
+            },
+        },
+    ])
 }
 
 #[cfg(test)]
@@ -33,6 +44,85 @@ mod tests {
         Day::test(Day::part2, "input", 8216)
     }
 
+    #[test]
+    fn render_test1() -> Result<()> {
+        Day::test(
+            |input| {
+                let contraption = Contraption::parse(input);
+                contraption.render(&Beams::new(&contraption).energized((0, 0), (1, 0)))
+            },
+            "test-1",
+            "\
+######....
+.#...#....
+.#...#####
+.#...##...
+.#...##...
+.#...##...
+.#..####..
+########..
+.#######..
+.#...#.#..
+"
+            .to_owned(),
+        )
+    }
+
+    #[test]
+    fn memoized_matches_trace() -> Result<()> {
+        // Following every beam without the cached splitters has to give the same result
+        fn brute_force(contraption: &Contraption, start: Position, direction: Direction) -> usize {
+            let mut energized = HashSet::new();
+            let mut queue = vec![(start, direction)];
+            while let Some((position, direction)) = queue.pop() {
+                if !contraption.inside(position) || !energized.insert((position, direction)) {
+                    continue;
+                }
+
+                let directions = match contraption.map.get(&position) {
+                    Some(&c) => Contraption::split(c, direction)
+                        .map(Vec::from)
+                        .unwrap_or_else(|| {
+                            vec![match (c, direction) {
+                                ('\\', (x, y)) => (y, x),
+                                ('/', (x, y)) => (-y, -x),
+                                _ => direction,
+                            }]
+                        }),
+                    None => vec![direction],
+                };
+                queue.extend(
+                    directions
+                        .into_iter()
+                        .map(|d| ((position.0 + d.0, position.1 + d.1), d)),
+                );
+            }
+
+            energized
+                .iter()
+                .map(|(position, _)| position)
+                .collect::<HashSet<_>>()
+                .len()
+        }
+
+        Day::test(
+            |input| {
+                let contraption = Contraption::parse(input);
+                let beams = Beams::new(&contraption);
+                contraption
+                    .edges()
+                    .into_iter()
+                    .step_by(7)
+                    .all(|(start, direction)| {
+                        beams.energized(start, direction).len()
+                            == brute_force(&contraption, start, direction)
+                    })
+            },
+            "input",
+            true,
+        )
+    }
+
     // Benchmarks
     extern crate test;
     #[bench]
@@ -47,154 +137,251 @@ mod tests {
     }
 }
 
-fn energize(
-    mut position: (isize, isize),
-    mut direction: (isize, isize),
-    size: (isize, isize),
-    map: &HashMap<(isize, isize), char>,
-    energized: &mut HashSet<((isize, isize), (isize, isize))>,
-) {
-    while position.0 >= 0 && position.0 < size.0 && position.1 >= 0 && position.1 < size.1 {
-        if !energized.insert((position, direction)) {
-            // We already proccessed this path before
-            break;
-        }
+type Position = (isize, isize);
+type Direction = (isize, isize);
 
-        if let Some(c) = map.get(&position) {
-            direction = match (c, direction) {
-                ('\\', (1, 0)) => (0, 1),
-                ('\\', (-1, 0)) => (0, -1),
-                ('\\', (0, 1)) => (1, 0),
-                ('\\', (0, -1)) => (-1, 0),
-                ('/', (1, 0)) => (0, -1),
-                ('/', (-1, 0)) => (0, 1),
-                ('/', (0, 1)) => (-1, 0),
-                ('/', (0, -1)) => (1, 0),
-                ('-', (1 | -1, 0)) => direction,
-                ('-', (0, 1 | -1)) => {
-                    energize((position.0 - 1, position.1), (-1, 0), size, map, energized);
-                    energize((position.0 + 1, position.1), (1, 0), size, map, energized);
-                    break;
-                }
-                ('|', (1 | -1, 0)) => {
-                    energize((position.0, position.1 - 1), (0, -1), size, map, energized);
-                    energize((position.0, position.1 + 1), (0, 1), size, map, energized);
-                    break;
-                }
-                ('|', (0, 1 | -1)) => direction,
-                _ => unreachable!("Invalid state"),
-            };
+// Fixed size set of cells
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cells {
+    bits: Vec<u64>,
+}
+
+impl Cells {
+    fn new(size: usize) -> Self {
+        Self {
+            bits: vec![0; size.div_ceil(64)],
         }
+    }
 
-        position.0 += direction.0;
-        position.1 += direction.1;
+    fn insert(&mut self, index: usize) {
+        self.bits[index / 64] |= 1 << (index % 64);
     }
-}
 
-fn energized(
-    start: (isize, isize),
-    direction: (isize, isize),
-    size: (isize, isize),
-    map: &HashMap<(isize, isize), char>,
-) -> usize {
-    let mut energized = HashSet::new();
-    energize(start, direction, size, &map, &mut energized);
-
-    energized
-        .iter()
-        .map(|(position, _)| position)
-        .collect::<HashSet<_>>()
-        .len()
-}
+    fn contains(&self, index: usize) -> bool {
+        self.bits[index / 64] & (1 << (index % 64)) != 0
+    }
 
-// -- Solution --
-pub struct Day;
-impl aoc::Solver for Day {
-    type Output1 = usize;
-    type Output2 = usize;
+    fn union(&mut self, other: &Cells) {
+        self.bits
+            .iter_mut()
+            .zip(&other.bits)
+            .for_each(|(a, b)| *a |= b);
+    }
 
-    fn day() -> u8 {
-        16
+    fn len(&self) -> usize {
+        self.bits
+            .iter()
+            .map(|bits| bits.count_ones() as usize)
+            .sum()
     }
+}
 
-    fn part1(input: &str) -> Self::Output1 {
-        let mut width = 0;
-        let mut height = 0;
+struct Contraption {
+    map: HashMap<Position, char>,
+    size: (isize, isize),
+}
+
+// Cells a beam passes through until it hits the flat side of a splitter (or leaves the map)
+struct Segment {
+    cells: Cells,
+    splitter: Option<Position>,
+}
 
+impl Contraption {
+    fn parse(input: &str) -> Self {
         let map = input
             .lines()
             .enumerate()
             .flat_map(|(y, line)| {
-                let y = y as isize;
-                if y + 1 > height {
-                    height = y + 1;
-                }
-
-                line.chars()
-                    .enumerate()
-                    .filter_map(|(x, c)| {
-                        let x = x as isize;
-                        if x + 1 > width {
-                            width = x + 1;
-                        }
-                        match c {
-                            '.' => None,
-                            '\\' | '/' | '|' | '-' => Some(((x, y), c)),
-                            _ => unreachable!(),
-                        }
-                    })
-                    .collect::<Vec<_>>()
+                line.chars().enumerate().filter_map(move |(x, c)| match c {
+                    '.' => None,
+                    '\\' | '/' | '|' | '-' => Some(((x as isize, y as isize), c)),
+                    _ => unreachable!(),
+                })
             })
             .collect::<HashMap<_, _>>();
 
-        energized((0, 0), (1, 0), (width, height), &map)
+        let width = input.lines().map(|line| line.len()).max().unwrap_or(0) as isize;
+        let height = input.lines().count() as isize;
+
+        Self {
+            map,
+            size: (width, height),
+        }
     }
 
-    fn part2(input: &str) -> Self::Output2 {
-        let mut width = 0;
-        let mut height = 0;
+    fn index(&self, position: Position) -> usize {
+        (position.1 * self.size.0 + position.0) as usize
+    }
 
-        let map = input
-            .lines()
-            .enumerate()
-            .flat_map(|(y, line)| {
-                let y = y as isize;
-                if y + 1 > height {
-                    height = y + 1;
+    fn inside(&self, position: Position) -> bool {
+        position.0 >= 0 && position.0 < self.size.0 && position.1 >= 0 && position.1 < self.size.1
+    }
+
+    // The two beams leaving a splitter, if the beam hits it on the flat side
+    fn split(c: char, direction: Direction) -> Option<[Direction; 2]> {
+        match (c, direction) {
+            ('-', (0, _)) => Some([(-1, 0), (1, 0)]),
+            ('|', (_, 0)) => Some([(0, -1), (0, 1)]),
+            _ => None,
+        }
+    }
+
+    fn trace(&self, mut position: Position, mut direction: Direction) -> Segment {
+        let mut cells = Cells::new((self.size.0 * self.size.1) as usize);
+        let mut visited = HashSet::new();
+
+        while self.inside(position) && visited.insert((position, direction)) {
+            cells.insert(self.index(position));
+
+            if let Some(&c) = self.map.get(&position) {
+                if Self::split(c, direction).is_some() {
+                    return Segment {
+                        cells,
+                        splitter: Some(position),
+                    };
                 }
 
-                line.chars()
-                    .enumerate()
-                    .filter_map(|(x, c)| {
-                        let x = x as isize;
-                        if x + 1 > width {
-                            width = x + 1;
-                        }
-                        match c {
-                            '.' => None,
-                            '\\' | '/' | '|' | '-' => Some(((x, y), c)),
-                            _ => unreachable!(),
-                        }
-                    })
-                    .collect::<Vec<_>>()
-            })
-            .collect::<HashMap<_, _>>();
+                direction = match (c, direction) {
+                    ('\\', (x, y)) => (y, x),
+                    ('/', (x, y)) => (-y, -x),
+                    _ => direction,
+                };
+            }
 
-        let mut max = 0;
-        for x in 0..width {
-            let a = energized((x, 0), (0, 1), (width, height), &map);
-            let b = energized((x, height - 1), (0, -1), (width, height), &map);
+            position.0 += direction.0;
+            position.1 += direction.1;
+        }
 
-            max = std::cmp::max(max, std::cmp::max(a, b));
+        Segment {
+            cells,
+            splitter: None,
         }
+    }
 
-        for y in 0..height {
-            let a = energized((0, y), (1, 0), (width, height), &map);
-            let b = energized((width - 1, y), (-1, 0), (width, height), &map);
+    // All positions on the edge with the direction pointing into the map
+    fn edges(&self) -> Vec<(Position, Direction)> {
+        let (width, height) = self.size;
+        (0..width)
+            .flat_map(|x| [((x, 0), (0, 1)), ((x, height - 1), (0, -1))])
+            .chain((0..height).flat_map(|y| [((0, y), (1, 0)), ((width - 1, y), (-1, 0))]))
+            .collect()
+    }
 
-            max = std::cmp::max(max, std::cmp::max(a, b));
+    fn render(&self, energized: &Cells) -> String {
+        let mut output = String::new();
+        for y in 0..self.size.1 {
+            for x in 0..self.size.0 {
+                output.push(if energized.contains(self.index((x, y))) {
+                    '#'
+                } else {
+                    '.'
+                });
+            }
+            output.push('\n');
         }
 
-        max
+        output
+    }
+}
+
+// Every splitter that gets hit on its flat side always sends out the same two beams, so the
+// cells energized from a splitter only have to be calculated once. Splitters can send beams to
+// each other in circles, these form strongly connected components that all energize the same
+// cells.
+struct Beams<'a> {
+    contraption: &'a Contraption,
+    // Energized cells for every splitter
+    energized: HashMap<Position, Cells>,
+}
+
+impl<'a> Beams<'a> {
+    fn new(contraption: &'a Contraption) -> Self {
+        let mut graph = DiGraphMap::<Position, ()>::new();
+        let mut cells = HashMap::new();
+        for (&position, &c) in contraption
+            .map
+            .iter()
+            .filter(|(_, c)| matches!(c, '|' | '-'))
+        {
+            graph.add_node(position);
+
+            let mut own = Cells::new((contraption.size.0 * contraption.size.1) as usize);
+            own.insert(contraption.index(position));
+
+            let [a, b] = Contraption::split(c, if c == '-' { (0, 1) } else { (1, 0) }).unwrap();
+            for direction in [a, b] {
+                let segment = contraption.trace(
+                    (position.0 + direction.0, position.1 + direction.1),
+                    direction,
+                );
+                own.union(&segment.cells);
+                if let Some(splitter) = segment.splitter {
+                    graph.add_edge(position, splitter, ());
+                }
+            }
+            cells.insert(position, own);
+        }
+
+        // The components come out in reverse topological order, so the components that a
+        // component leads to are always done first
+        let mut energized = HashMap::new();
+        for component in tarjan_scc(&graph) {
+            let mut total = Cells::new((contraption.size.0 * contraption.size.1) as usize);
+            for splitter in &component {
+                total.union(&cells[splitter]);
+                for next in graph.neighbors(*splitter) {
+                    if let Some(next) = energized.get(&next) {
+                        total.union(next);
+                    }
+                }
+            }
+
+            for splitter in component {
+                energized.insert(splitter, total.clone());
+            }
+        }
+
+        Self {
+            contraption,
+            energized,
+        }
+    }
+
+    fn energized(&self, start: Position, direction: Direction) -> Cells {
+        let mut segment = self.contraption.trace(start, direction);
+        if let Some(splitter) = segment.splitter {
+            segment.cells.union(&self.energized[&splitter]);
+        }
+
+        segment.cells
+    }
+}
+
+// -- Solution --
+pub struct Day;
+impl aoc::Solver for Day {
+    type Output1 = usize;
+    type Output2 = usize;
+
+    fn day() -> u8 {
+        16
+    }
+
+    fn part1(input: &str) -> Self::Output1 {
+        let contraption = Contraption::parse(input);
+        Beams::new(&contraption).energized((0, 0), (1, 0)).len()
+    }
+
+    fn part2(input: &str) -> Self::Output2 {
+        let contraption = Contraption::parse(input);
+        let beams = Beams::new(&contraption);
+
+        contraption
+            .edges()
+            .into_iter()
+            .map(|(start, direction)| beams.energized(start, direction).len())
+            .max()
+            .unwrap_or(0)
     }
 }
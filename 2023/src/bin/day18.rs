@@ -1,11 +1,9 @@
 #![feature(test)]
-use std::{
-    collections::{HashSet, VecDeque},
-    fmt::Pointer,
-};
-
 use anyhow::Result;
-use aoc::Solver;
+use aoc::{
+    polygon::{Direction, Polygon},
+    Solver,
+};
 
 // -- Runners --
 fn main() -> Result<()> {
@@ -36,6 +34,44 @@ mod tests {
         Day::test(Day::part2, "input", 92291468914147)
     }
 
+    #[test]
+    fn polygon_test1() -> Result<()> {
+        Day::test(
+            |input| {
+                let steps = input.lines().map(|line| {
+                    let mut parts = line.split(' ');
+                    let direction = match parts.next().unwrap() {
+                        "U" => Direction::Up,
+                        "D" => Direction::Down,
+                        "L" => Direction::Left,
+                        _ => Direction::Right,
+                    };
+                    (direction, parts.next().unwrap().parse().unwrap())
+                });
+                let polygon = Polygon::from_steps(steps).unwrap();
+                (
+                    polygon.vertices().len(),
+                    polygon.area(),
+                    polygon.boundary_points(),
+                    polygon.interior_points(),
+                )
+            },
+            "test-1",
+            (14, 42, 38, 24),
+        )
+    }
+
+    #[test]
+    fn invalid_plan() {
+        use Direction::*;
+
+        assert!(lagoon([(Right, 2), (Down, 2), (Left, 1)].into_iter()).is_err());
+        assert_eq!(
+            lagoon([(Right, 3), (Down, 3), (Left, 3), (Up, 3)].into_iter()).unwrap(),
+            16
+        );
+    }
+
     // Benchmarks
     extern crate test;
     #[bench]
@@ -50,6 +86,14 @@ mod tests {
     }
 }
 
+// The trench is one cube wide, so the lagoon is every grid point on or inside the polygon
+fn lagoon(steps: impl Iterator<Item = (Direction, i128)>) -> Result<usize> {
+    let polygon = Polygon::from_steps(steps)?;
+    polygon.validate()?;
+
+    Ok(polygon.lattice_points() as usize)
+}
+
 // -- Solution --
 pub struct Day;
 impl aoc::Solver for Day {
@@ -61,91 +105,39 @@ impl aoc::Solver for Day {
     }
 
     fn part1(input: &str) -> Self::Output1 {
-        let instructions: Vec<_> = input
-            .lines()
-            .map(|line| {
-                let (direction, rest) = line.split_once(' ').unwrap();
-                let direction = direction.chars().next().unwrap();
-                let (distance, _) = rest.split_once(" (#").unwrap();
-                let distance: usize = distance.parse().unwrap();
-
-                (direction, distance)
-            })
-            .collect();
-
-        let mut location = (0_isize, 0_isize);
-        let mut points = Vec::new();
-        let mut boundary = 0;
-
-        for (direction, distance) in &instructions {
-            for _ in 0..*distance {
-                match direction {
-                    'U' => location.1 -= 1,
-                    'D' => location.1 += 1,
-                    'L' => location.0 -= 1,
-                    'R' => location.0 += 1,
-                    _ => unreachable!("Invalid input"),
-                }
-            }
-            boundary += distance;
-            points.push(location);
-        }
-
-        let mut area = 0;
-        for i in 0..points.len() as isize {
-            let n_min = (i - 1).rem_euclid(points.len() as isize);
-            let n_plus = (i + 1).rem_euclid(points.len() as isize);
-
-            area += points[i as usize].1 * (points[n_min as usize].0 - points[n_plus as usize].0);
-        }
-        let area = area as usize / 2;
-        let interior = area - boundary / 2 + 1;
-
-        interior + boundary
+        let steps = input.lines().map(|line| {
+            let (direction, rest) = line.split_once(' ').unwrap();
+            let direction = match direction {
+                "U" => Direction::Up,
+                "D" => Direction::Down,
+                "L" => Direction::Left,
+                "R" => Direction::Right,
+                _ => unreachable!("Invalid input"),
+            };
+            let (distance, _) = rest.split_once(" (#").unwrap();
+
+            (direction, distance.parse().unwrap())
+        });
+
+        lagoon(steps).unwrap()
     }
 
     fn part2(input: &str) -> Self::Output2 {
-        let instructions: Vec<_> = input
-            .lines()
-            .map(|line| {
-                let (_, rest) = line.split_once(' ').unwrap();
-                let (_, rest) = rest.split_once(" (#").unwrap();
-
-                let (distance, direction) = rest.split_at(5);
-                let distance = usize::from_str_radix(distance, 16).unwrap();
-                let direction = direction.chars().next().unwrap();
-
-                (direction, distance)
-            })
-            .collect();
-
-        let mut location = (0_isize, 0_isize);
-        let mut points = Vec::new();
-        let mut boundary = 0;
-
-        for (direction, distance) in &instructions {
-            for _ in 0..*distance {
-                match direction {
-                    '3' => location.1 -= 1,
-                    '1' => location.1 += 1,
-                    '2' => location.0 -= 1,
-                    '0' => location.0 += 1,
-                    _ => unreachable!("Invalid input"),
-                }
-            }
-            boundary += distance;
-            points.push(location);
-        }
-
-        let mut area = 0;
-        for i in 0..points.len() as isize {
-            let n_min = (i - 1).rem_euclid(points.len() as isize);
-            let n_plus = (i + 1).rem_euclid(points.len() as isize);
-
-            area += points[i as usize].1 * (points[n_min as usize].0 - points[n_plus as usize].0);
-        }
-        let area = area as usize / 2;
-
-        area + boundary / 2 + 1
+        let steps = input.lines().map(|line| {
+            let (_, rest) = line.split_once(" (#").unwrap();
+
+            let (distance, direction) = rest.split_at(5);
+            let direction = match direction.chars().next().unwrap() {
+                '3' => Direction::Up,
+                '1' => Direction::Down,
+                '2' => Direction::Left,
+                '0' => Direction::Right,
+                _ => unreachable!("Invalid input"),
+            };
+
+            (direction, i128::from_str_radix(distance, 16).unwrap())
+        });
+
+        lagoon(steps).unwrap()
     }
 }
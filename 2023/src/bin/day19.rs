@@ -1,12 +1,26 @@
 #![feature(test)]
-use std::collections::{HashMap, VecDeque};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Write,
+};
 
-use anyhow::Result;
-use aoc::Solver;
+use anyhow::{Context, Result};
+use aoc::{Output, Solver};
 
 // -- Runners --
 fn main() -> Result<()> {
-    Day::solve()
+    Day::solve_with(&[
+        // Print the simplified workflows as a Graphviz graph instead of the answers
+        Output {
+            name: "dot",
+            run: |input, _| {
+                let (workflows, _) = input.split_once("\n\n").context("Missing parts")?;
+                Ok(Decision::compile(&parse_workflows(workflows))
+                    .simplify()
+                    .dot())
+            },
+        },
+    ])
 }
 
 #[cfg(test)]
@@ -33,6 +47,137 @@ mod tests {
         Day::test(Day::part2, "input", 131550418841958)
     }
 
+    fn decision(input: &str) -> Decision {
+        Decision::compile(&parse_workflows(input.split_once("\n\n").unwrap().0))
+    }
+
+    #[test]
+    fn simplify_test1() -> Result<()> {
+        Day::test(
+            |input| {
+                let decision = decision(input);
+                let simplified = decision.simplify();
+
+                // Every part that was accepted before is still accepted
+                let mut before = decision
+                    .accepted()
+                    .iter()
+                    .map(Part2::combinations)
+                    .sum::<usize>();
+                let mut after = simplified
+                    .accepted()
+                    .iter()
+                    .map(Part2::combinations)
+                    .sum::<usize>();
+                assert_eq!(before, after);
+
+                // The example has no dead rules, lnx and gd are already merged while compiling
+                before = decision.size();
+                after = simplified.size();
+                (before, after)
+            },
+            "test-1",
+            (13, 13),
+        )
+    }
+
+    #[test]
+    fn simplify_removes_dead_rules() {
+        // The second test can never be true, after which both sides accept
+        let decision = decision("in{x<10:a,R}\na{x>20:R,m<5:A,A}\n\n");
+        let simplified = decision.simplify();
+        assert_eq!(
+            simplified.nodes[simplified.root],
+            Node::Branch {
+                category: Category::Cool,
+                threshold: 10,
+                below: 0,
+                above: 1
+            }
+        );
+        assert_eq!(simplified.size(), 3);
+    }
+
+    #[test]
+    fn simplify_shared() {
+        // Every level tests one of the categories and then continues with the next level either
+        // way, so there are 26^4 paths through the graph that all share the same nodes
+        let workflows = (0..100)
+            .map(|i| {
+                let category = ["x", "m", "a", "s"][i % 4];
+                let threshold = 10 * (i / 4 + 1);
+                let next = if i == 99 {
+                    "A".to_owned()
+                } else {
+                    format!("l{}", i + 1)
+                };
+                format!("l{i}{{{category}<{threshold}:{next},{category}>3999:R,{next}}}\n")
+            })
+            .collect::<String>();
+        let decision = decision(&format!("in{{x<4000:l0,R}}\n{workflows}\n"));
+
+        let simplified = decision.simplify();
+        for x in (1..4000).step_by(97) {
+            for m in (1..4000).step_by(89) {
+                for (a, s) in [(5, 4000), (300, 15), (4000, 4000)] {
+                    let part = Part { x, m, a, s };
+                    assert_eq!(simplified.accepts(&part), decision.accepts(&part));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn accepted_ranges() {
+        let decision = decision("in{x<10:a,R}\na{s>20:R,A}\n\n");
+        assert_eq!(
+            decision.accepted(),
+            vec![Part2 {
+                x: (1, 9),
+                m: (1, 4000),
+                a: (1, 4000),
+                s: (1, 20)
+            }]
+        );
+    }
+
+    #[test]
+    fn dot_test1() -> Result<()> {
+        Day::test(
+            |input| {
+                let dot = decision(input).simplify().dot();
+                (
+                    dot.starts_with("digraph workflows {\n") && dot.ends_with("}\n"),
+                    dot.matches("[label=\"yes\"]").count(),
+                    dot.matches("[label=\"no\"]").count(),
+                    dot.matches("shape=box").count(),
+                )
+            },
+            "test-1",
+            (true, 11, 11, 2),
+        )
+    }
+
+    #[test]
+    fn dot_small() {
+        let dot = decision("in{x<10:a,R}\na{s>20:R,A}\n\n").simplify().dot();
+        assert_eq!(
+            dot,
+            "\
+digraph workflows {
+    n0 [label=\"A\", shape=box, color=green];
+    n1 [label=\"R\", shape=box, color=red];
+    n2 [label=\"s<21\"];
+    n2 -> n0 [label=\"yes\"];
+    n2 -> n1 [label=\"no\"];
+    n3 [label=\"x<10\"];
+    n3 -> n2 [label=\"yes\"];
+    n3 -> n1 [label=\"no\"];
+}
+"
+        );
+    }
+
     // Benchmarks
     extern crate test;
     #[bench]
@@ -66,9 +211,18 @@ impl Part {
     fn sum(&self) -> usize {
         self.x + self.m + self.a + self.s
     }
+
+    fn get(&self, category: Category) -> usize {
+        match category {
+            Category::Cool => self.x,
+            Category::Musical => self.m,
+            Category::Aerodynamic => self.a,
+            Category::Shiny => self.s,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct Part2 {
     x: (usize, usize),
     m: (usize, usize),
@@ -77,13 +231,27 @@ struct Part2 {
 }
 
 impl Part2 {
-    fn split(&self, category: Category, value: usize) -> (Option<Part2>, Option<Part2>) {
-        let range = match category {
+    fn full() -> Self {
+        Part2 {
+            x: (1, 4000),
+            m: (1, 4000),
+            a: (1, 4000),
+            s: (1, 4000),
+        }
+    }
+
+    fn range(&self, category: Category) -> (usize, usize) {
+        match category {
             Category::Cool => self.x,
             Category::Musical => self.m,
             Category::Aerodynamic => self.a,
             Category::Shiny => self.s,
-        };
+        }
+    }
+
+    // Split into the values below the given value and the values equal or above
+    fn split(&self, category: Category, value: usize) -> (Option<Part2>, Option<Part2>) {
+        let range = self.range(category);
 
         let ranges = if range.0 > value {
             (None, Some(range))
@@ -99,6 +267,24 @@ impl Part2 {
         )
     }
 
+    // Widen the ranges as far as possible without changing which side of the given thresholds
+    // (indexed by category) they end up on
+    fn widen(&self, thresholds: &[BTreeSet<usize>; 4]) -> Part2 {
+        let mut bounds = *self;
+        for category in Category::ALL {
+            let (low, high) = self.range(category);
+            let tested = &thresholds[category as usize];
+            let low = tested.range(..low).next_back().map_or(1, |value| value + 1);
+            let high = tested
+                .range(high + 1..)
+                .next()
+                .map_or(4000, |value| value - 1);
+            bounds = bounds.replace(category, Some((low, high))).unwrap();
+        }
+
+        bounds
+    }
+
     fn replace(&self, category: Category, range: Option<(usize, usize)>) -> Option<Part2> {
         range.map(|range| match category {
             Category::Cool => Part2 {
@@ -143,7 +329,7 @@ enum Operator {
     Otherwise,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum Category {
     Cool,
     Musical,
@@ -151,35 +337,22 @@ enum Category {
     Shiny,
 }
 
+impl Category {
+    const ALL: [Category; 4] = [
+        Category::Cool,
+        Category::Musical,
+        Category::Aerodynamic,
+        Category::Shiny,
+    ];
+}
+
 #[derive(Debug, Clone, Copy)]
 struct Step<'a> {
     op: Operator,
     next: Next<'a>,
 }
 
-impl<'a> Step<'a> {
-    fn process(&self, part: &Part) -> Option<Next<'a>> {
-        let ok = match self.op {
-            Operator::GreaterThan(Category::Cool, value) => part.x > value,
-            Operator::GreaterThan(Category::Musical, value) => part.m > value,
-            Operator::GreaterThan(Category::Aerodynamic, value) => part.a > value,
-            Operator::GreaterThan(Category::Shiny, value) => part.s > value,
-            Operator::LessThan(Category::Cool, value) => part.x < value,
-            Operator::LessThan(Category::Musical, value) => part.m < value,
-            Operator::LessThan(Category::Aerodynamic, value) => part.a < value,
-            Operator::LessThan(Category::Shiny, value) => part.s < value,
-            Operator::Otherwise => true,
-        };
-
-        if ok {
-            Some(self.next)
-        } else {
-            None
-        }
-    }
-}
-
-fn process_step(step: &str) -> Step {
+fn process_step(step: &str) -> Step<'_> {
     if let Some((instruction, next)) = step.split_once(':') {
         let next = match next {
             "A" => Next::Accept,
@@ -218,6 +391,321 @@ fn process_step(step: &str) -> Step {
     }
 }
 
+fn parse_workflows(workflows: &str) -> HashMap<&str, Vec<Step<'_>>> {
+    workflows
+        .lines()
+        .map(|workflow| {
+            let (name, mut rest) = workflow.split_once('{').unwrap();
+
+            let mut steps = Vec::new();
+
+            while let Some((step, remaining)) = rest.split_once(',') {
+                rest = remaining;
+                steps.push(process_step(step));
+            }
+
+            steps.push(process_step(rest.split_once('}').unwrap().0));
+
+            (name, steps)
+        })
+        .collect()
+}
+
+type NodeId = usize;
+
+// Node in the decision graph, every branch tests if the value of a category is below a threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Node {
+    Accept,
+    Reject,
+    Branch {
+        category: Category,
+        threshold: usize,
+        below: NodeId,
+        above: NodeId,
+    },
+}
+
+// All workflows compiled into a single decision graph.
+// Identical nodes are only stored once, so equal sub-trees are shared.
+#[derive(Debug, Clone)]
+struct Decision {
+    nodes: Vec<Node>,
+    ids: HashMap<Node, NodeId>,
+    root: NodeId,
+}
+
+impl Decision {
+    const ACCEPT: NodeId = 0;
+    const REJECT: NodeId = 1;
+
+    fn empty() -> Self {
+        let mut decision = Self {
+            nodes: Vec::new(),
+            ids: HashMap::new(),
+            root: Self::REJECT,
+        };
+        decision.node(Node::Accept);
+        decision.node(Node::Reject);
+
+        decision
+    }
+
+    fn compile(workflows: &HashMap<&str, Vec<Step>>) -> Self {
+        fn compile_step<'a>(
+            decision: &mut Decision,
+            workflows: &HashMap<&'a str, Vec<Step<'a>>>,
+            memo: &mut HashMap<(&'a str, usize), NodeId>,
+            name: &'a str,
+            idx: usize,
+        ) -> NodeId {
+            if let Some(&id) = memo.get(&(name, idx)) {
+                return id;
+            }
+
+            let step = workflows.get(name).unwrap()[idx];
+            let next = match step.next {
+                Next::Step(name) => compile_step(decision, workflows, memo, name, 0),
+                Next::Accept => Decision::ACCEPT,
+                Next::Reject => Decision::REJECT,
+            };
+
+            let id = match step.op {
+                Operator::Otherwise => next,
+                Operator::LessThan(category, value) => {
+                    let otherwise = compile_step(decision, workflows, memo, name, idx + 1);
+                    decision.branch(category, value, next, otherwise)
+                }
+                Operator::GreaterThan(category, value) => {
+                    let otherwise = compile_step(decision, workflows, memo, name, idx + 1);
+                    decision.branch(category, value + 1, otherwise, next)
+                }
+            };
+
+            memo.insert((name, idx), id);
+            id
+        }
+
+        let mut decision = Self::empty();
+        decision.root = compile_step(&mut decision, workflows, &mut HashMap::new(), "in", 0);
+
+        decision
+    }
+
+    fn node(&mut self, node: Node) -> NodeId {
+        *self.ids.entry(node).or_insert_with(|| {
+            self.nodes.push(node);
+            self.nodes.len() - 1
+        })
+    }
+
+    // A branch where both sides lead to the same node is not needed
+    fn branch(
+        &mut self,
+        category: Category,
+        threshold: usize,
+        below: NodeId,
+        above: NodeId,
+    ) -> NodeId {
+        if below == above {
+            below
+        } else {
+            self.node(Node::Branch {
+                category,
+                threshold,
+                below,
+                above,
+            })
+        }
+    }
+
+    // Remove tests that always have the same outcome given the earlier tests, and merge
+    // branches that end up being identical
+    fn simplify(&self) -> Self {
+        // A node only depends on the thresholds tested below it, so the bounds are widened up to
+        // those before looking in the memo. That way shared nodes are only rebuilt once for every
+        // way the thresholds can be split, instead of once for every path that reaches them.
+        fn rebuild(
+            old: &Decision,
+            new: &mut Decision,
+            memo: &mut HashMap<(NodeId, Part2), NodeId>,
+            thresholds: &[[BTreeSet<usize>; 4]],
+            id: NodeId,
+            bounds: Part2,
+        ) -> NodeId {
+            let bounds = bounds.widen(&thresholds[id]);
+            if let Some(&id) = memo.get(&(id, bounds)) {
+                return id;
+            }
+
+            let rebuilt = match old.nodes[id] {
+                Node::Accept => Decision::ACCEPT,
+                Node::Reject => Decision::REJECT,
+                Node::Branch {
+                    category,
+                    threshold,
+                    below,
+                    above,
+                } => match bounds.split(category, threshold) {
+                    (Some(bounds), None) => rebuild(old, new, memo, thresholds, below, bounds),
+                    (None, Some(bounds)) => rebuild(old, new, memo, thresholds, above, bounds),
+                    (Some(low), Some(high)) => {
+                        let below = rebuild(old, new, memo, thresholds, below, low);
+                        let above = rebuild(old, new, memo, thresholds, above, high);
+                        new.branch(category, threshold, below, above)
+                    }
+                    (None, None) => unreachable!("Bounds are never empty"),
+                },
+            };
+
+            memo.insert((id, bounds), rebuilt);
+            rebuilt
+        }
+
+        let mut decision = Self::empty();
+        let mut memo = HashMap::new();
+        let thresholds = self.thresholds();
+        decision.root = rebuild(
+            self,
+            &mut decision,
+            &mut memo,
+            &thresholds,
+            self.root,
+            Part2::full(),
+        );
+
+        decision
+    }
+
+    // All thresholds that are tested in the sub-graph of every node, indexed by category
+    fn thresholds(&self) -> Vec<[BTreeSet<usize>; 4]> {
+        // Nodes are only created after their children, so those are always done already
+        let mut thresholds: Vec<[BTreeSet<usize>; 4]> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let mut tested = <[BTreeSet<usize>; 4]>::default();
+            if let Node::Branch {
+                category,
+                threshold,
+                below,
+                above,
+            } = *node
+            {
+                for (tested, (below, above)) in tested
+                    .iter_mut()
+                    .zip(thresholds[below].iter().zip(&thresholds[above]))
+                {
+                    tested.extend(below.union(above));
+                }
+                tested[category as usize].insert(threshold);
+            }
+            thresholds.push(tested);
+        }
+
+        thresholds
+    }
+
+    fn accepts(&self, part: &Part) -> bool {
+        let mut id = self.root;
+        loop {
+            match self.nodes[id] {
+                Node::Accept => return true,
+                Node::Reject => return false,
+                Node::Branch {
+                    category,
+                    threshold,
+                    below,
+                    above,
+                } => {
+                    id = if part.get(category) < threshold {
+                        below
+                    } else {
+                        above
+                    };
+                }
+            }
+        }
+    }
+
+    // All disjoint ranges of parts that get accepted
+    fn accepted(&self) -> Vec<Part2> {
+        let mut accepted = Vec::new();
+        let mut stack = vec![(self.root, Part2::full())];
+        while let Some((id, bounds)) = stack.pop() {
+            match self.nodes[id] {
+                Node::Accept => accepted.push(bounds),
+                Node::Reject => {}
+                Node::Branch {
+                    category,
+                    threshold,
+                    below,
+                    above,
+                } => {
+                    let (low, high) = bounds.split(category, threshold);
+                    stack.extend(low.map(|low| (below, low)));
+                    stack.extend(high.map(|high| (above, high)));
+                }
+            }
+        }
+
+        accepted
+    }
+
+    // Number of nodes that can be reached from the root
+    #[cfg(test)]
+    fn size(&self) -> usize {
+        self.reachable().len()
+    }
+
+    fn reachable(&self) -> Vec<NodeId> {
+        let mut seen = vec![false; self.nodes.len()];
+        let mut stack = vec![self.root];
+        while let Some(id) = stack.pop() {
+            if std::mem::replace(&mut seen[id], true) {
+                continue;
+            }
+            if let Node::Branch { below, above, .. } = self.nodes[id] {
+                stack.extend([below, above]);
+            }
+        }
+
+        (0..self.nodes.len()).filter(|id| seen[*id]).collect()
+    }
+
+    // Export as a Graphviz graph
+    fn dot(&self) -> String {
+        let mut output = String::from("digraph workflows {\n");
+        for id in self.reachable() {
+            match self.nodes[id] {
+                Node::Accept => {
+                    writeln!(output, "    n{id} [label=\"A\", shape=box, color=green];").unwrap()
+                }
+                Node::Reject => {
+                    writeln!(output, "    n{id} [label=\"R\", shape=box, color=red];").unwrap()
+                }
+                Node::Branch {
+                    category,
+                    threshold,
+                    below,
+                    above,
+                } => {
+                    let category = match category {
+                        Category::Cool => 'x',
+                        Category::Musical => 'm',
+                        Category::Aerodynamic => 'a',
+                        Category::Shiny => 's',
+                    };
+                    writeln!(output, "    n{id} [label=\"{category}<{threshold}\"];").unwrap();
+                    writeln!(output, "    n{id} -> n{below} [label=\"yes\"];").unwrap();
+                    writeln!(output, "    n{id} -> n{above} [label=\"no\"];").unwrap();
+                }
+            }
+        }
+        output += "}\n";
+
+        output
+    }
+}
+
 // -- Solution --
 pub struct Day;
 impl aoc::Solver for Day {
@@ -231,23 +719,7 @@ impl aoc::Solver for Day {
     fn part1(input: &str) -> Self::Output1 {
         let (workflows, parts) = input.split_once("\n\n").unwrap();
 
-        let workflows = workflows
-            .lines()
-            .map(|workflow| {
-                let (name, mut rest) = workflow.split_once('{').unwrap();
-
-                let mut steps = Vec::new();
-
-                while let Some((step, remaining)) = rest.split_once(',') {
-                    rest = remaining;
-                    steps.push(process_step(step));
-                }
-
-                steps.push(process_step(rest.split_once('}').unwrap().0));
-
-                (name, steps)
-            })
-            .collect::<HashMap<_, _>>();
+        let decision = Decision::compile(&parse_workflows(workflows));
 
         let parts = parts
             .lines()
@@ -278,91 +750,19 @@ impl aoc::Solver for Day {
 
         parts
             .iter()
-            .map(|part| {
-                let mut workflow = workflows.get("in").unwrap();
-
-                while let Some(next) = workflow.iter().find_map(|step| step.process(part)) {
-                    match next {
-                        Next::Step(name) => workflow = workflows.get(name).unwrap(),
-                        Next::Accept => return part.sum(),
-                        Next::Reject => return 0,
-                    }
-                }
-
-                0
-            })
+            .filter(|part| decision.accepts(part))
+            .map(Part::sum)
             .sum()
     }
 
     fn part2(input: &str) -> Self::Output2 {
         let (workflows, _) = input.split_once("\n\n").unwrap();
 
-        let workflows = workflows
-            .lines()
-            .map(|workflow| {
-                let (name, mut rest) = workflow.split_once('{').unwrap();
-
-                let mut steps = Vec::new();
-
-                while let Some((step, remaining)) = rest.split_once(',') {
-                    rest = remaining;
-                    steps.push(process_step(step));
-                }
-
-                steps.push(process_step(rest.split_once('}').unwrap().0));
-
-                (name, steps)
-            })
-            .collect::<HashMap<_, _>>();
-
-        let part = Part2 {
-            x: (1, 4000),
-            m: (1, 4000),
-            a: (1, 4000),
-            s: (1, 4000),
-        };
-
-        let mut queue = VecDeque::new();
-        queue.push_back((part, "in"));
-
-        let mut combinations = 0;
-        while let Some((mut part, name)) = queue.pop_front() {
-            let workflow = workflows.get(name).unwrap();
-
-            for step in workflow {
-                let (ok, fail) = match step.op {
-                    Operator::GreaterThan(category, value) => {
-                        let (a, b) = part.split(category, value + 1);
-
-                        (b, a)
-                    }
-                    Operator::LessThan(category, value) => {
-                        let (a, b) = part.split(category, value);
-
-                        (a, b)
-                    }
-                    Operator::Otherwise => (Some(part), None),
-                };
-
-                // The part that matches goes to the next workflow
-                if let Some(ok) = ok {
-                    match step.next {
-                        Next::Step(name) => queue.push_back((ok, name)),
-                        Next::Accept => {
-                            let a = ok.combinations();
-                            combinations += a;
-                        }
-                        Next::Reject => {}
-                    }
-                }
-
-                // The part that failed goes to the next step
-                if let Some(fail) = fail {
-                    part = fail;
-                }
-            }
-        }
-
-        combinations
+        Decision::compile(&parse_workflows(workflows))
+            .simplify()
+            .accepted()
+            .iter()
+            .map(Part2::combinations)
+            .sum()
     }
 }
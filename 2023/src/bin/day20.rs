@@ -1,12 +1,27 @@
 #![feature(test)]
-use std::collections::{HashMap, VecDeque};
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    fmt::Write,
+    fs, io,
+};
 
-use anyhow::Result;
-use aoc::Solver;
+use anyhow::{anyhow, bail, Context, Result};
+use aoc::{Output, Solver};
 
 // -- Runners --
 fn main() -> Result<()> {
-    Day::solve()
+    Day::solve_with(&[
+        // Print the circuit as a Graphviz graph instead of the answers
+        Output {
+            name: "dot",
+            run: |input, _| Ok(Circuit::parse(input)?.dot()),
+        },
+        // Step through the circuit using commands from stdin
+        Output {
+            name: "debug",
+            run: |input, _| debug(input),
+        },
+    ])
 }
 
 #[cfg(test)]
@@ -33,6 +48,113 @@ mod tests {
         Day::test(Day::part2, "input", 253302889093151)
     }
 
+    #[test]
+    fn counters() -> Result<()> {
+        Day::test(
+            |input| {
+                let circuit = Circuit::parse(input).unwrap();
+                analyze(&circuit, "rx")
+                    .unwrap()
+                    .iter()
+                    .map(|counter| counter.period == counter.phase)
+                    .collect::<Vec<_>>()
+            },
+            "input",
+            vec![true; 4],
+        )
+    }
+
+    #[test]
+    fn unexpected_structure() -> Result<()> {
+        Day::test(
+            |input| {
+                let circuit = Circuit::parse(input).unwrap();
+                analyze(&circuit, "rx").unwrap_err().to_string()
+            },
+            "test-2",
+            "Expected a single module feeding 'rx', found 0".to_owned(),
+        )
+    }
+
+    #[test]
+    fn chinese_remainder() {
+        let counter = |phase, period| Counter {
+            name: "test",
+            period,
+            phase,
+        };
+        assert_eq!(combine(&[counter(3, 3), counter(5, 5)]).unwrap(), 15);
+        assert_eq!(combine(&[counter(2, 3), counter(3, 4)]).unwrap(), 11);
+        assert_eq!(combine(&[counter(4, 6), counter(7, 9)]).unwrap(), 16);
+        assert_eq!(combine(&[counter(7, 3)]).unwrap(), 7);
+        assert!(combine(&[counter(1, 4), counter(2, 6)]).is_err());
+    }
+
+    #[test]
+    fn dot_test2() -> Result<()> {
+        Day::test(
+            |input| Circuit::parse(input).unwrap().dot(),
+            "test-2",
+            "digraph circuit {
+    a [shape=ellipse];
+    b [shape=ellipse];
+    broadcaster [shape=diamond];
+    con [shape=box];
+    inv [shape=box];
+    a -> con;
+    a -> inv;
+    b -> con;
+    broadcaster -> a;
+    con -> output;
+    inv -> b;
+}
+"
+            .to_owned(),
+        )
+    }
+
+    #[test]
+    fn debugger_test2() -> Result<()> {
+        Day::test(
+            |input| {
+                let mut debugger = Debugger::new(Circuit::parse(input).unwrap());
+                ["show", "watch con", "press 5", "show con", "unwatch con", "press 3", "snapshot", "show nope"]
+                    .into_iter()
+                    .map(|command| match debugger.execute(command) {
+                        Ok(output) => output,
+                        Err(err) => format!("Error: {err}"),
+                    })
+                    .collect::<Vec<_>>()
+            },
+            "test-2",
+            vec![
+                "%a: off\n%b: off\n&con: a=low, b=low\n&inv: a=low".to_owned(),
+                "Watching con".to_owned(),
+                "Presses: 1\nWatchpoint: a sent a high pulse to con during press 1\nWatchpoint: b sent a high pulse to con during press 1".to_owned(),
+                "&con: a=high, b=high".to_owned(),
+                "Stopped watching con".to_owned(),
+                "Presses: 4".to_owned(),
+                r#"{"presses": 4, "modules": {"a": {"type": "flip-flop", "on": false}, "b": {"type": "flip-flop", "on": false}, "con": {"type": "conjunction", "inputs": {"a": false, "b": false}}, "inv": {"type": "conjunction", "inputs": {"a": false}}}}"#.to_owned(),
+                "Error: Unknown module 'nope'".to_owned(),
+            ],
+        )
+    }
+
+    #[test]
+    fn watch_unknown() -> Result<()> {
+        Day::test(
+            |input| {
+                let mut debugger = Debugger::new(Circuit::parse(input).unwrap());
+                (
+                    debugger.watch("output").is_ok(),
+                    debugger.watch("nope").is_err(),
+                )
+            },
+            "test-2",
+            (true, true),
+        )
+    }
+
     // Benchmarks
     extern crate test;
     #[bench]
@@ -89,186 +211,479 @@ struct Pulse<'a> {
     high: bool,
 }
 
-// Copied from day 8
-fn gcd(a: usize, b: usize) -> usize {
-    if b == 0 {
-        return a;
-    }
-
-    gcd(b, a % b)
-}
-
-// Copied from day 8
-fn lcm(a: usize, b: usize) -> usize {
-    if a > b {
-        (a / gcd(a, b)) * b
-    } else {
-        (b / gcd(a, b)) * a
-    }
+#[derive(Debug, Clone)]
+struct Circuit<'a> {
+    modules: HashMap<&'a str, Module<'a>>,
+    presses: usize,
 }
 
-// -- Solution --
-pub struct Day;
-impl aoc::Solver for Day {
-    type Output1 = usize;
-    type Output2 = usize;
-
-    fn day() -> u8 {
-        20
-    }
-
-    fn part1(input: &str) -> Self::Output1 {
+impl<'a> Circuit<'a> {
+    fn parse(input: &'a str) -> Result<Self> {
         let mut modules: HashMap<_, _> = input
             .lines()
             .map(|line| {
-                let (name, destinations) = line.split_once(" -> ").unwrap();
+                let (name, destinations) = line
+                    .split_once(" -> ")
+                    .ok_or_else(|| anyhow!("Invalid line '{line}'"))?;
                 let destinations: Vec<_> = destinations.split(", ").collect();
 
-                let (name, module_type) = if name.starts_with('%') {
-                    (name.split_at(1).1, ModuleType::FlipFlop(false))
-                } else if name.starts_with('&') {
-                    (name.split_at(1).1, ModuleType::Conjunction(HashMap::new()))
+                let (name, module_type) = if let Some(name) = name.strip_prefix('%') {
+                    (name, ModuleType::FlipFlop(false))
+                } else if let Some(name) = name.strip_prefix('&') {
+                    (name, ModuleType::Conjunction(HashMap::new()))
                 } else if name == "broadcaster" {
                     (name, ModuleType::Broadcaster)
                 } else {
-                    unreachable!("Invalid input");
+                    bail!("Unknown module type for '{name}'");
                 };
 
-                (
+                Ok((
                     name,
                     Module {
                         module_type,
                         destinations,
                     },
-                )
+                ))
             })
-            .collect();
+            .collect::<Result<_>>()?;
 
-        // TODO: Because of the borrow check we have to create a clone of modules here
-        for (name, module) in modules.clone() {
-            for destination in &module.destinations {
-                if let Some(module) = modules.get_mut(destination) {
-                    if let ModuleType::Conjunction(inputs) = &mut module.module_type {
-                        inputs.insert(name, false);
-                    }
+        for (name, destination) in Self::edges(&modules) {
+            if let Some(module) = modules.get_mut(destination) {
+                if let ModuleType::Conjunction(inputs) = &mut module.module_type {
+                    inputs.insert(name, false);
                 }
             }
         }
 
-        let mut count = (0, 0);
-        let mut pulses = VecDeque::new();
-        for _ in 0..1000 {
-            pulses.push_back(Pulse {
-                source: "button",
-                destination: "broadcaster",
-                high: false,
-            });
+        Ok(Self {
+            modules,
+            presses: 0,
+        })
+    }
 
-            while let Some(pulse) = pulses.pop_front() {
-                if pulse.high {
-                    count.1 += 1;
-                } else {
-                    count.0 += 1;
-                }
+    // All connections sorted by source and destination, this keeps the output deterministic
+    fn edges(modules: &HashMap<&'a str, Module<'a>>) -> Vec<(&'a str, &'a str)> {
+        let mut edges: Vec<_> = modules
+            .iter()
+            .flat_map(|(name, module)| {
+                module
+                    .destinations
+                    .iter()
+                    .map(|destination| (*name, *destination))
+            })
+            .collect();
+        edges.sort();
+
+        edges
+    }
 
-                if let Some(module) = modules.get_mut(pulse.destination) {
-                    if let Some(high) = module.process(pulse.source, pulse.high) {
-                        for destination in &module.destinations {
-                            pulses.push_back(Pulse {
-                                source: pulse.destination,
-                                destination,
-                                high,
-                            });
-                        }
+    // Modules that send pulses to the given module
+    fn inputs(&self, name: &str) -> Vec<&'a str> {
+        Self::edges(&self.modules)
+            .into_iter()
+            .filter(|(_, destination)| *destination == name)
+            .map(|(source, _)| source)
+            .collect()
+    }
+
+    // Push the button once, every pulse is passed to the observer when it is delivered
+    fn press(&mut self, mut observer: impl FnMut(&Pulse<'a>)) {
+        self.presses += 1;
+
+        let mut pulses = VecDeque::new();
+        pulses.push_back(Pulse {
+            source: "button",
+            destination: "broadcaster",
+            high: false,
+        });
+
+        while let Some(pulse) = pulses.pop_front() {
+            observer(&pulse);
+
+            if let Some(module) = self.modules.get_mut(pulse.destination) {
+                if let Some(high) = module.process(pulse.source, pulse.high) {
+                    for destination in &module.destinations {
+                        pulses.push_back(Pulse {
+                            source: pulse.destination,
+                            destination,
+                            high,
+                        });
                     }
                 }
             }
         }
+    }
 
-        count.0 * count.1
+    // Export as a Graphviz graph, the shape shows the type of the module
+    fn dot(&self) -> String {
+        let mut names: Vec<_> = self.modules.keys().copied().collect();
+        names.sort();
+
+        let mut output = String::from("digraph circuit {\n");
+        for name in names {
+            let shape = match self.modules[name].module_type {
+                ModuleType::Broadcaster => "diamond",
+                ModuleType::FlipFlop(_) => "ellipse",
+                ModuleType::Conjunction(_) => "box",
+            };
+            writeln!(output, "    {name} [shape={shape}];").unwrap();
+        }
+        for (source, destination) in Self::edges(&self.modules) {
+            writeln!(output, "    {source} -> {destination};").unwrap();
+        }
+        output += "}\n";
+
+        output
     }
+}
 
-    fn part2(input: &str) -> Self::Output2 {
-        let mut modules: HashMap<_, _> = input
-            .lines()
-            .map(|line| {
-                let (name, destinations) = line.split_once(" -> ").unwrap();
-                let destinations: Vec<_> = destinations.split(", ").collect();
+// A sub-circuit that sends a high pulse to the final conjunction on presses phase, phase + period, ...
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Counter<'a> {
+    name: &'a str,
+    period: usize,
+    phase: usize,
+}
 
-                let (name, module_type) = if name.starts_with('%') {
-                    (name.split_at(1).1, ModuleType::FlipFlop(false))
-                } else if name.starts_with('&') {
-                    (name.split_at(1).1, ModuleType::Conjunction(HashMap::new()))
-                } else if name == "broadcaster" {
-                    (name, ModuleType::Broadcaster)
-                } else {
-                    unreachable!("Invalid input");
-                };
+// Number of presses after which to give up looking for a counter to fire
+const MAX_PRESSES: usize = 100_000;
+
+// Occurrences of every counter needed to confirm that it is periodic
+const CONFIRMATIONS: usize = 3;
+
+// The module that sends the pulse to the target has to be a conjunction that is fed by a number of
+// independent counters. Every counter is simulated until its period can be confirmed.
+fn analyze<'a>(circuit: &Circuit<'a>, target: &str) -> Result<Vec<Counter<'a>>> {
+    let feeding = circuit.inputs(target);
+    let [last] = feeding.as_slice() else {
+        bail!(
+            "Expected a single module feeding '{target}', found {}",
+            feeding.len()
+        );
+    };
+    if !matches!(
+        circuit.modules[last].module_type,
+        ModuleType::Conjunction(_)
+    ) {
+        bail!("Expected '{last}' feeding '{target}' to be a conjunction");
+    }
 
-                (
-                    name,
-                    Module {
-                        module_type,
-                        destinations,
-                    },
-                )
+    let counters = circuit.inputs(last);
+    let mut hits: HashMap<&str, Vec<usize>> =
+        counters.iter().map(|name| (*name, Vec::new())).collect();
+
+    let mut circuit = circuit.clone();
+    while hits.values().any(|hits| hits.len() < CONFIRMATIONS) {
+        if circuit.presses == MAX_PRESSES {
+            let (name, _) = hits
+                .iter()
+                .find(|(_, hits)| hits.len() < CONFIRMATIONS)
+                .unwrap();
+            bail!(
+                "Counter '{name}' did not fire {CONFIRMATIONS} times within {MAX_PRESSES} presses"
+            );
+        }
+
+        let presses = circuit.presses + 1;
+        circuit.press(|pulse| {
+            if pulse.destination == *last && pulse.high {
+                let hits = hits.get_mut(pulse.source).unwrap();
+                // A counter can fire multiple times during a single press
+                if hits.last() != Some(&presses) {
+                    hits.push(presses);
+                }
+            }
+        });
+    }
+
+    counters
+        .into_iter()
+        .map(|name| {
+            let hits = &hits[name];
+            let period = hits[1] - hits[0];
+            if hits.windows(2).any(|pair| pair[1] - pair[0] != period) {
+                bail!("Counter '{name}' is not periodic, it fired on presses {hits:?}");
+            }
+
+            Ok(Counter {
+                name,
+                period,
+                phase: hits[0],
             })
-            .collect();
+        })
+        .collect()
+}
 
-        // TODO: Because of the borrow check we have to create a clone of modules here
-        for (name, module) in modules.clone() {
-            for destination in &module.destinations {
-                if let Some(module) = modules.get_mut(destination) {
-                    if let ModuleType::Conjunction(inputs) = &mut module.module_type {
-                        inputs.insert(name, false);
-                    }
+fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        return (a, 1, 0);
+    }
+
+    let (gcd, x, y) = extended_gcd(b, a % b);
+    (gcd, y, x - (a / b) * y)
+}
+
+// Find the first press on which all counters fire, using the chinese remainder theorem.
+// The moduli do not have to be coprime.
+fn combine(counters: &[Counter]) -> Result<usize> {
+    let (mut remainder, mut modulus) = (0_i128, 1_i128);
+    for counter in counters {
+        let (phase, period) = (counter.phase as i128, counter.period as i128);
+        let (gcd, x, _) = extended_gcd(modulus, period);
+        if (phase - remainder) % gcd != 0 {
+            bail!(
+                "Counter '{}' never fires together with the other counters",
+                counter.name
+            );
+        }
+
+        let lcm = modulus / gcd * period;
+        remainder =
+            (remainder + (phase - remainder) / gcd * x % (period / gcd) * modulus).rem_euclid(lcm);
+        modulus = lcm;
+    }
+
+    // The counters only start firing at their phase
+    let start = counters
+        .iter()
+        .map(|counter| counter.phase)
+        .max()
+        .unwrap_or(0) as i128;
+    if remainder < start {
+        remainder += (start - remainder + modulus - 1) / modulus * modulus;
+    }
+
+    Ok(remainder as usize)
+}
+
+// A high pulse that was delivered to a watched module
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Watch<'a> {
+    press: usize,
+    source: &'a str,
+    destination: &'a str,
+}
+
+// Step through the circuit one button press at a time
+struct Debugger<'a> {
+    circuit: Circuit<'a>,
+    watchpoints: BTreeSet<String>,
+}
+
+impl<'a> Debugger<'a> {
+    fn new(circuit: Circuit<'a>) -> Self {
+        Self {
+            circuit,
+            watchpoints: BTreeSet::new(),
+        }
+    }
+
+    fn watch(&mut self, name: &str) -> Result<()> {
+        let known =
+            self.circuit.modules.contains_key(name) || !self.circuit.inputs(name).is_empty();
+        if !known {
+            bail!("Unknown module '{name}'");
+        }
+
+        self.watchpoints.insert(name.to_owned());
+        Ok(())
+    }
+
+    // Press the button up to count times, stops after the press in which a watchpoint was hit
+    fn press(&mut self, count: usize) -> Vec<Watch<'a>> {
+        let mut hits = Vec::new();
+        for _ in 0..count {
+            let press = self.circuit.presses + 1;
+            let watchpoints = &self.watchpoints;
+            self.circuit.press(|pulse| {
+                if pulse.high && watchpoints.contains(pulse.destination) {
+                    hits.push(Watch {
+                        press,
+                        source: pulse.source,
+                        destination: pulse.destination,
+                    });
                 }
+            });
+
+            if !hits.is_empty() {
+                break;
             }
         }
 
-        let r#final = modules
+        hits
+    }
+
+    // Names of all modules that have memory, sorted
+    fn stateful(&self) -> Vec<&'a str> {
+        let mut names: Vec<_> = self
+            .circuit
+            .modules
             .iter()
-            .find(|(_, module)| module.destinations.contains(&"rx"))
-            .unwrap();
+            .filter(|(_, module)| !matches!(module.module_type, ModuleType::Broadcaster))
+            .map(|(name, _)| *name)
+            .collect();
+        names.sort();
 
-        let final_name = r#final.0.to_owned();
-        let final_module = r#final.1.clone();
-        let mut frequencies = HashMap::new();
+        names
+    }
 
-        println!("{final_name}: {final_module:?}");
+    // Sorted inputs of a conjunction with the last pulse that was received from them
+    fn remembered(inputs: &HashMap<&'a str, bool>) -> Vec<(&'a str, bool)> {
+        let mut inputs: Vec<_> = inputs.iter().map(|(name, high)| (*name, *high)).collect();
+        inputs.sort();
 
-        let mut pulses = VecDeque::new();
-        for i in 0..10000 {
-            pulses.push_back(Pulse {
-                source: "button",
-                destination: "broadcaster",
-                high: false,
-            });
+        inputs
+    }
 
-            while let Some(pulse) = pulses.pop_front() {
-                if let Some(module) = modules.get_mut(pulse.destination) {
-                    if pulse.destination == final_name && pulse.high {
-                        frequencies.insert(pulse.source, i + 1);
-                        if let ModuleType::Conjunction(ref inputs) = final_module.module_type {
-                            if frequencies.len() == inputs.len() {
-                                return frequencies.values().copied().fold(1, lcm);
-                            }
-                        }
-                    }
+    // Human readable memory of a single module
+    fn show(&self, name: &str) -> Result<String> {
+        let module = self
+            .circuit
+            .modules
+            .get(name)
+            .ok_or_else(|| anyhow!("Unknown module '{name}'"))?;
+        let level = |high| if high { "high" } else { "low" };
+
+        Ok(match &module.module_type {
+            ModuleType::Broadcaster => format!("{name}: broadcaster"),
+            ModuleType::FlipFlop(on) => format!("%{name}: {}", if *on { "on" } else { "off" }),
+            ModuleType::Conjunction(inputs) => {
+                let inputs = Self::remembered(inputs)
+                    .into_iter()
+                    .map(|(input, high)| format!("{input}={}", level(high)))
+                    .collect::<Vec<_>>();
+                format!("&{name}: {}", inputs.join(", "))
+            }
+        })
+    }
 
-                    if let Some(high) = module.process(pulse.source, pulse.high) {
-                        for destination in &module.destinations {
-                            pulses.push_back(Pulse {
-                                source: pulse.destination,
-                                destination,
-                                high,
-                            });
-                        }
-                    }
+    // Memory of every module as JSON
+    fn snapshot(&self) -> String {
+        let modules = self
+            .stateful()
+            .into_iter()
+            .map(|name| match &self.circuit.modules[name].module_type {
+                ModuleType::FlipFlop(on) => {
+                    format!("\"{name}\": {{\"type\": \"flip-flop\", \"on\": {on}}}")
+                }
+                ModuleType::Conjunction(inputs) => {
+                    let inputs = Self::remembered(inputs)
+                        .into_iter()
+                        .map(|(input, high)| format!("\"{input}\": {high}"))
+                        .collect::<Vec<_>>();
+                    format!(
+                        "\"{name}\": {{\"type\": \"conjunction\", \"inputs\": {{{}}}}}",
+                        inputs.join(", ")
+                    )
                 }
+                ModuleType::Broadcaster => unreachable!("The broadcaster has no memory"),
+            })
+            .collect::<Vec<_>>();
+
+        format!(
+            "{{\"presses\": {}, \"modules\": {{{}}}}}",
+            self.circuit.presses,
+            modules.join(", ")
+        )
+    }
+
+    // Run a single command, returns the text to show to the user
+    fn execute(&mut self, command: &str) -> Result<String> {
+        let words: Vec<_> = command.split_whitespace().collect();
+        let output = match words.as_slice() {
+            ["press"] | ["press", _] => {
+                let count = words.get(1).map_or(Ok(1), |count| count.parse()).context("Invalid number of presses")?;
+                let hits = self.press(count);
+
+                let mut output = format!("Presses: {}", self.circuit.presses);
+                for hit in hits {
+                    write!(output, "\nWatchpoint: {} sent a high pulse to {} during press {}", hit.source, hit.destination, hit.press)?;
+                }
+                output
+            }
+            ["watch", name] => {
+                self.watch(name)?;
+                format!("Watching {name}")
             }
+            ["unwatch", name] => {
+                if !self.watchpoints.remove(*name) {
+                    bail!("Not watching '{name}'");
+                }
+                format!("Stopped watching {name}")
+            }
+            ["show"] => self
+                .stateful()
+                .into_iter()
+                .map(|name| self.show(name))
+                .collect::<Result<Vec<_>>>()?
+                .join("\n"),
+            ["show", name] => self.show(name)?,
+            ["snapshot"] => self.snapshot(),
+            ["snapshot", path] => {
+                fs::write(path, self.snapshot() + "\n")?;
+                format!("Saved snapshot to {path}")
+            }
+            _ => bail!("Unknown command '{command}', expected press [n], watch <module>, unwatch <module>, show [module] or snapshot [file]"),
+        };
+
+        Ok(output)
+    }
+}
+
+// Read debugger commands from stdin until it is closed or 'quit' is entered, this also works
+// with a script piped in
+// Every command is answered right away, so nothing is left to print once the session ends
+fn debug(input: &str) -> Result<String> {
+    let mut debugger = Debugger::new(Circuit::parse(input)?);
+    for line in io::stdin().lines() {
+        let line = line?;
+        let command = line.trim();
+        match command {
+            "" => continue,
+            "quit" => break,
+            _ => {}
+        }
+
+        match debugger.execute(command) {
+            Ok(output) => println!("{output}"),
+            Err(err) => println!("Error: {err}"),
         }
+    }
+
+    Ok(String::new())
+}
+
+// -- Solution --
+pub struct Day;
+impl aoc::Solver for Day {
+    type Output1 = usize;
+    type Output2 = usize;
+
+    fn day() -> u8 {
+        20
+    }
+
+    fn part1(input: &str) -> Self::Output1 {
+        let mut circuit = Circuit::parse(input).unwrap();
+
+        let mut count = (0, 0);
+        for _ in 0..1000 {
+            circuit.press(|pulse| {
+                if pulse.high {
+                    count.1 += 1;
+                } else {
+                    count.0 += 1;
+                }
+            });
+        }
+
+        count.0 * count.1
+    }
+
+    fn part2(input: &str) -> Self::Output2 {
+        let circuit = Circuit::parse(input).unwrap();
+        let counters = analyze(&circuit, "rx").unwrap();
 
-        unreachable!("No solution found");
+        combine(&counters).unwrap()
     }
 }
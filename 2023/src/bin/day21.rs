@@ -1,7 +1,7 @@
 #![feature(test)]
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::VecDeque;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use aoc::Solver;
 
 // -- Runners --
@@ -11,8 +11,34 @@ fn main() -> Result<()> {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use super::*;
 
+    // Walk the infinite garden one plot at a time
+    fn brute_force(garden: &Garden, steps: u64) -> u64 {
+        let mut distances = HashMap::from([(garden.start, 0)]);
+        let mut queue = VecDeque::from([(garden.start, 0)]);
+        while let Some(((x, y), distance)) = queue.pop_front() {
+            if distance == steps {
+                continue;
+            }
+
+            for (dx, dy) in DIRECTIONS {
+                let next = (x + dx, y + dy);
+                if !garden.is_rock(next) && !distances.contains_key(&next) {
+                    distances.insert(next, distance + 1);
+                    queue.push_back((next, distance + 1));
+                }
+            }
+        }
+
+        distances
+            .values()
+            .filter(|&&distance| distance % 2 == steps % 2)
+            .count() as u64
+    }
+
     #[test]
     fn part1_test1() -> Result<()> {
         // The example only provides an answer for 6 steps. (16)
@@ -25,7 +51,67 @@ mod tests {
         Day::test(Day::part1, "input", 3642)
     }
 
-    // There is no test case for part 2
+    // There is no test case for part 2, but the puzzle gives the counts for a few step counts
+    #[test]
+    fn reachable_test1() -> Result<()> {
+        Day::test(
+            |input| {
+                let garden = Garden::parse(input);
+                [6, 10, 50, 100, 500, 1000, 5000].map(|steps| garden.reachable(steps).unwrap())
+            },
+            "test-1",
+            [16, 50, 1594, 6536, 167004, 668697, 16733044],
+        )
+    }
+
+    #[test]
+    fn brute_force_test1() -> Result<()> {
+        Day::test(
+            |input| {
+                let garden = Garden::parse(input);
+                (0..60).all(|steps| garden.reachable(steps).unwrap() == brute_force(&garden, steps))
+            },
+            "test-1",
+            true,
+        )
+    }
+
+    #[test]
+    fn brute_force_solution() -> Result<()> {
+        Day::test(
+            |input| {
+                let garden = Garden::parse(input);
+                [0, 1, 64, 65, 130, 131, 327]
+                    .iter()
+                    .all(|&steps| garden.reachable(steps).unwrap() == brute_force(&garden, steps))
+            },
+            "input",
+            true,
+        )
+    }
+
+    #[test]
+    fn off_centre() {
+        // Not square, the start is not in the middle and the edges are not free
+        let garden = Garden::parse(
+            "\
+.#......#
+...##..#.
+.S..#....
+.#...##.#
+...#.....
+#....#.#.
+.........",
+        );
+
+        for steps in 0..80 {
+            assert_eq!(
+                garden.reachable(steps).unwrap(),
+                brute_force(&garden, steps),
+                "{steps} steps"
+            );
+        }
+    }
 
     #[test]
     fn part2_solution() -> Result<()> {
@@ -46,129 +132,305 @@ mod tests {
     }
 }
 
-// -- Solution --
-pub struct Day;
-impl aoc::Solver for Day {
-    type Output1 = usize;
-    type Output2 = isize;
+const DIRECTIONS: [(i64, i64); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
 
-    fn day() -> u8 {
-        21
-    }
+#[derive(Debug, Clone)]
+struct Garden {
+    width: i64,
+    height: i64,
+    start: (i64, i64),
+    rocks: Vec<bool>,
+}
 
-    fn part1(input: &str) -> Self::Output1 {
-        let mut queue = Vec::new();
-        let map: HashMap<(isize, isize), char> = input
+impl Garden {
+    fn parse(input: &str) -> Self {
+        let width = input.lines().next().map_or(0, |line| line.len()) as i64;
+        let height = input.lines().count() as i64;
+
+        let mut start = (0, 0);
+        let rocks = input
             .lines()
             .enumerate()
-            .flat_map(|(y, line)| {
-                line.chars()
-                    .enumerate()
-                    .map(|(x, mut c)| {
-                        if c == 'S' {
-                            queue.push((x as isize, y as isize));
-                            c = '.';
-                        }
-
-                        ((x as isize, y as isize), c)
-                    })
-                    .collect::<Vec<_>>()
+            .flat_map(|(y, line)| line.chars().enumerate().map(move |(x, c)| (x, y, c)))
+            .map(|(x, y, c)| {
+                if c == 'S' {
+                    start = (x as i64, y as i64);
+                }
+
+                c == '#'
             })
             .collect();
 
-        let mut set = HashSet::new();
-        let directions = [(-1, 0), (1, 0), (0, -1), (0, 1)];
-        for _ in 0..64 {
-            while let Some(step) = queue.pop() {
-                for direction in directions {
-                    let next = (step.0 + direction.0, step.1 + direction.1);
-
-                    // If the tile is free add it to the queue
-                    if let Some(&tile) = map.get(&next) {
-                        if tile == '.' {
-                            set.insert(next);
-                        }
-                    }
+        Self {
+            width,
+            height,
+            start,
+            rocks,
+        }
+    }
+
+    // The garden repeats infinitely in every direction
+    fn is_rock(&self, (x, y): (i64, i64)) -> bool {
+        let (x, y) = (x.rem_euclid(self.width), y.rem_euclid(self.height));
+        self.rocks[(y * self.width + x) as usize]
+    }
+
+    // Shortest distance to every plot in the tiles up to `radius` away from the start tile.
+    // Paths can not leave this block, so only the tiles near the middle are exact.
+    fn distances(&self, radius: i64) -> Block<'_> {
+        let tiles = 2 * radius + 1;
+        let (width, height) = (tiles * self.width, tiles * self.height);
+
+        let mut distances = vec![None; (width * height) as usize];
+        let start = (
+            radius * self.width + self.start.0,
+            radius * self.height + self.start.1,
+        );
+        distances[(start.1 * width + start.0) as usize] = Some(0);
+
+        let mut queue = VecDeque::from([start]);
+        while let Some((x, y)) = queue.pop_front() {
+            let distance = distances[(y * width + x) as usize].unwrap();
+
+            for (dx, dy) in DIRECTIONS {
+                let next = (x + dx, y + dy);
+                if next.0 < 0
+                    || next.1 < 0
+                    || next.0 >= width
+                    || next.1 >= height
+                    || self.is_rock(next)
+                {
+                    continue;
+                }
+
+                let index = (next.1 * width + next.0) as usize;
+                if distances[index].is_none() {
+                    distances[index] = Some(distance + 1);
+                    queue.push_back(next);
                 }
             }
+        }
 
-            queue = set.into_iter().collect();
-            set = HashSet::new();
+        Block {
+            garden: self,
+            radius,
+            distances,
         }
+    }
+
+    // Number of plots that can be reached in exactly the given number of steps on the infinite
+    // garden. The distances are calculated for a block of tiles around the start, far enough out
+    // every tile is exactly one tile length further away than its neighbour towards the start.
+    fn reachable(&self, steps: u64) -> Result<u64> {
+        const MAX_RADIUS: i64 = 16;
 
-        queue.len()
+        let mut radius = 2;
+        while radius <= MAX_RADIUS {
+            // One extra ring of tiles to check that the distances are periodic
+            if let Some(count) = self.distances(radius + 1).count(radius, steps) {
+                return Ok(count);
+            }
+
+            radius *= 2;
+        }
+
+        bail!("Distances did not become periodic within {MAX_RADIUS} tiles of the start")
     }
+}
 
-    fn part2(input: &str) -> Self::Output2 {
-        // All maps are square
-        let size = input.lines().count();
-        // Map is square: 131 x 131 => size = 131
-        // !!! Others have observed 26501365 = 202300 * size + size/2 !!!
-        // Is there a pattern for i * size + size/2?
-        // i = 0 => 3776
-        // i = 1 => 33652
-        // i = 2 => 93270
-        // Is there a function of i that can fit to this?
-        // Yes => Quadratic equation will fit this
-        //      3642 - 14737 x + 14871 x^2
-        // i = 202300 => 608603023105276
-        // Could not have done this without a hint from Reddit...
-        let i: isize = 202300;
-
-        let mut queue = Vec::new();
-        let map: HashMap<(isize, isize), char> = input
-            .lines()
-            .enumerate()
-            .flat_map(|(y, line)| {
-                line.chars()
-                    .enumerate()
-                    .map(|(x, mut c)| {
-                        if c == 'S' {
-                            queue.push((x as isize, y as isize));
-                            c = '.';
-                        }
-
-                        ((x as isize, y as isize), c)
-                    })
-                    .collect::<Vec<_>>()
+struct Block<'a> {
+    garden: &'a Garden,
+    radius: i64,
+    distances: Vec<Option<u64>>,
+}
+
+impl Block<'_> {
+    // Distances to all plots in a tile, tile (0, 0) contains the start
+    fn tile(&self, (tx, ty): (i64, i64)) -> impl Iterator<Item = Option<u64>> + '_ {
+        let (width, height) = (self.garden.width, self.garden.height);
+        let stride = (2 * self.radius + 1) * width;
+        let (left, top) = ((tx + self.radius) * width, (ty + self.radius) * height);
+
+        (top..top + height).flat_map(move |y| {
+            (left..left + width).map(move |x| self.distances[(y * stride + x) as usize])
+        })
+    }
+
+    // Check that every plot in a tile is the same number of steps further away than in another
+    // tile. Plots that can not be reached have to be unreachable in both.
+    fn offset(&self, from: (i64, i64), to: (i64, i64), offset: &mut Option<u64>) -> bool {
+        self.tile(from)
+            .zip(self.tile(to))
+            .all(|distances| match distances {
+                (None, None) => true,
+                (Some(a), Some(b)) if b > a => *offset.get_or_insert(b - a) == b - a,
+                _ => false,
             })
-            .collect();
+    }
 
-        let mut nums = Vec::new();
-        let mut set = HashSet::new();
-        let directions = [(-1, 0), (1, 0), (0, -1), (0, 1)];
-        for n in 0..(2 * size + size / 2) {
-            while let Some(step) = queue.pop() {
-                for direction in directions {
-                    let next = (step.0 + direction.0, step.1 + direction.1);
-
-                    let next_wrapped = (
-                        next.0.rem_euclid(size as isize),
-                        next.1.rem_euclid(size as isize),
-                    );
-
-                    // If the tile is free add it to the queue
-                    if let Some(&tile) = map.get(&next_wrapped) {
-                        if tile == '.' {
-                            set.insert(next);
-                        }
-                    }
-                }
+    // Count the reachable plots using the tiles up to `radius` from the start as a reference.
+    // Returns [None] if the distances are not periodic beyond that radius.
+    fn count(&self, radius: i64, steps: u64) -> Option<u64> {
+        let reachable =
+            |distance: &Option<u64>| distance.is_some_and(|d| d <= steps && d % 2 == steps % 2);
+
+        // Every tile inside the radius is counted directly
+        let mut count = 0;
+        for ty in -radius..=radius {
+            for tx in -radius..=radius {
+                count += self.tile((tx, ty)).filter(reachable).count() as u64;
             }
+        }
 
-            queue = set.into_iter().collect();
-            set = HashSet::new();
+        // The distance added by moving one tile further out in every direction
+        let mut offsets = [None; 4];
+        for (offset, (dx, dy)) in offsets.iter_mut().zip(DIRECTIONS) {
+            // Every tile on the edge of the radius in this direction
+            let valid = (-radius..=radius).all(|i| {
+                let edge = (dx * radius + dy.abs() * i, dy * radius + dx.abs() * i);
+                self.offset(edge, (edge.0 + dx, edge.1 + dy), offset)
+            });
+
+            if !valid {
+                return None;
+            }
+        }
 
-            if n + 1 == nums.len() * size + size / 2 {
-                nums.push(queue.len() as isize);
+        // Beyond the edge the tiles form a strip in every direction
+        for ((dx, dy), offset) in DIRECTIONS.into_iter().zip(offsets) {
+            let offset = offset.unwrap_or(1);
+            for i in -radius..=radius {
+                let edge = (dx * radius + dy.abs() * i, dy * radius + dx.abs() * i);
+                count += self
+                    .tile(edge)
+                    .flatten()
+                    .map(|distance| strip(distance + offset, offset, steps))
+                    .sum::<u64>();
             }
         }
 
-        // Using linear algebra these solutions can be found
-        let a = (nums[0] - 2 * nums[1] + nums[2]) / 2;
-        let b = (4 * nums[1] - 3 * nums[0] - nums[2]) / 2;
-        let c = nums[0];
+        // Between the strips are four quadrants, these also have to agree with moving diagonally
+        for (horizontal, vertical) in [(0, 2), (0, 3), (1, 2), (1, 3)] {
+            let (dx, dy) = (DIRECTIONS[horizontal].0, DIRECTIONS[vertical].1);
+            let corner = (dx * radius, dy * radius);
+            let (x_offset, y_offset) = (
+                offsets[horizontal].unwrap_or(1),
+                offsets[vertical].unwrap_or(1),
+            );
+
+            let mut diagonal = Some(x_offset + y_offset);
+            if !self.offset(corner, (corner.0 + dx, corner.1 + dy), &mut diagonal) {
+                return None;
+            }
+
+            count += self
+                .tile(corner)
+                .flatten()
+                .map(|distance| quadrant(distance + x_offset + y_offset, x_offset, y_offset, steps))
+                .sum::<u64>();
+        }
+
+        Some(count)
+    }
+}
+
+// Number of n >= 0 for which `start + n * step` is at most `steps` and has the same parity
+fn strip(start: u64, step: u64, steps: u64) -> u64 {
+    let Some(remaining) = steps.checked_sub(start) else {
+        return 0;
+    };
+
+    let last = remaining / step;
+    if step.is_multiple_of(2) {
+        // The parity never changes
+        return if remaining.is_multiple_of(2) {
+            last + 1
+        } else {
+            0
+        };
+    }
+
+    // Only every other n has the right parity
+    let first = remaining % 2;
+    if last >= first {
+        (last - first) / 2 + 1
+    } else {
+        0
+    }
+}
+
+// Number of a, b >= 0 for which `start + a * x + b * y` is at most `steps` and has the same parity.
+// For every a the b values are counted like a strip, this is summed over a in closed form.
+fn quadrant(start: u64, x: u64, y: u64, steps: u64) -> u64 {
+    let Some(remaining) = steps.checked_sub(start) else {
+        return 0;
+    };
+
+    // Split the values of a by parity, within each class the parity of the remaining steps is fixed
+    let last = remaining / x;
+    (0..2)
+        .filter(|&first| first <= last)
+        .map(|first| {
+            let count = (last - first) / 2 + 1;
+            let left = remaining - first * x;
+
+            // Remaining steps for the largest a, the others add 2 * x each
+            let smallest = left - 2 * x * (count - 1);
+            if y.is_multiple_of(2) {
+                // Strips with an odd number of remaining steps are empty
+                if left % 2 == 1 {
+                    return 0;
+                }
+
+                // Sum of smallest / y + 1 for every a
+                floor_sum(count, y, 2 * x, smallest + y)
+            } else {
+                // Sum of (smallest - parity * y) / 2y + 1 for every a, shifted to stay positive
+                let parity = left % 2;
+                floor_sum(count, 2 * y, 2 * x, smallest + 2 * y - parity * y)
+            }
+        })
+        .sum()
+}
+
+// Sum of (a * i + b) / m for i in 0..n, in logarithmic time
+fn floor_sum(n: u64, m: u64, a: u64, b: u64) -> u64 {
+    if n == 0 {
+        return 0;
+    }
+
+    let mut result = (a / m) * n * (n - 1) / 2 + (b / m) * n;
+    let (a, b) = (a % m, b % m);
+
+    let max = a * n + b;
+    if max >= m {
+        result += floor_sum(max / m, a, m, max % m);
+    }
+
+    result
+}
 
-        a * i.pow(2) + b * i + c
+// -- Solution --
+pub struct Day;
+impl aoc::Solver for Day {
+    type Output1 = usize;
+    type Output2 = u64;
+
+    fn day() -> u8 {
+        21
+    }
+
+    fn part1(input: &str) -> Self::Output1 {
+        // Only the tile with the start
+        let garden = Garden::parse(input);
+        garden
+            .distances(0)
+            .tile((0, 0))
+            .filter(|distance| matches!(distance, Some(d) if *d <= 64 && d % 2 == 0))
+            .count()
+    }
+
+    fn part2(input: &str) -> Self::Output2 {
+        Garden::parse(input).reachable(26501365).unwrap()
     }
 }
@@ -1,12 +1,38 @@
 #![feature(test)]
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::Write,
+};
 
-use anyhow::Result;
-use aoc::Solver;
+use anyhow::{bail, Context, Result};
+use aoc::{Output, Solver};
 
 // -- Runners --
 fn main() -> Result<()> {
-    Day::solve()
+    Day::solve_with(&[
+        // Print the settled bricks as voxels instead of the answers
+        Output {
+            name: "voxels",
+            run: |input, _| Ok(Stack::settle(input).voxels()),
+        },
+        // Print the bricks that fall when the given brick is removed, counting from the bottom
+        Output {
+            name: "falls",
+            run: |input, brick| {
+                let brick = brick.context("Missing brick, use --falls=<brick>")?;
+                let brick = brick
+                    .parse()
+                    .with_context(|| format!("Invalid brick '{brick}'"))?;
+                let stack = Stack::settle(input);
+                if brick >= stack.bricks.len() {
+                    bail!("There are only {} bricks", stack.bricks.len());
+                }
+
+                let falling = stack.falls_if_removed(brick);
+                Ok(format!("{} bricks fall: {falling:?}\n", falling.len()))
+            },
+        },
+    ])
 }
 
 #[cfg(test)]
@@ -33,6 +59,92 @@ mod tests {
         Day::test(Day::part2, "input", 79465)
     }
 
+    #[test]
+    fn support_graph() -> Result<()> {
+        Day::test(
+            |input| {
+                let stack = Stack::settle(input);
+                let supporting = stack.supporting();
+                (stack.supports, supporting)
+            },
+            "test-1",
+            (
+                vec![
+                    vec![],
+                    vec![0],
+                    vec![0],
+                    vec![1, 2],
+                    vec![1, 2],
+                    vec![3, 4],
+                    vec![5],
+                ],
+                vec![
+                    vec![1, 2],
+                    vec![3, 4],
+                    vec![3, 4],
+                    vec![5],
+                    vec![5],
+                    vec![6],
+                    vec![],
+                ],
+            ),
+        )
+    }
+
+    #[test]
+    fn chain_reactions_test1() -> Result<()> {
+        Day::test(
+            |input| {
+                let stack = Stack::settle(input);
+                let simulated = (0..stack.bricks.len())
+                    .map(|brick| stack.falls_if_removed(brick).len())
+                    .collect::<Vec<_>>();
+                assert_eq!(stack.falls_if_removed(0), vec![1, 2, 3, 4, 5, 6]);
+                (simulated, stack.chain_reactions())
+            },
+            "test-1",
+            (vec![6, 0, 0, 0, 0, 1, 0], vec![6, 0, 0, 0, 0, 1, 0]),
+        )
+    }
+
+    #[test]
+    fn chain_reactions_solution() -> Result<()> {
+        Day::test(
+            |input| {
+                let stack = Stack::settle(input);
+                let simulated = (0..stack.bricks.len())
+                    .map(|brick| stack.falls_if_removed(brick).len())
+                    .collect::<Vec<_>>();
+                simulated == stack.chain_reactions()
+            },
+            "input",
+            true,
+        )
+    }
+
+    #[test]
+    fn voxels_test1() -> Result<()> {
+        Day::test(
+            |input| {
+                let voxels = Stack::settle(input).voxels();
+                // The brick that starts at height 5 lands at height 3
+                (
+                    voxels.lines().count(),
+                    voxels
+                        .lines()
+                        .nth(15)
+                        .unwrap()
+                        .rsplit_once(' ')
+                        .unwrap()
+                        .0
+                        .to_owned(),
+                )
+            },
+            "test-1",
+            (21, "2 2 3".to_owned()),
+        )
+    }
+
     // Benchmarks
     extern crate test;
     #[bench]
@@ -54,11 +166,29 @@ struct Brick {
 }
 
 impl Brick {
+    fn parse(line: &str) -> Self {
+        let (start, end) = line.split_once('~').unwrap();
+        let coordinate = |s: &str| {
+            let c: Vec<_> = s
+                .splitn(3, ',')
+                .map(|num| num.parse::<isize>().unwrap())
+                .collect();
+            (c[0], c[1], c[2])
+        };
+
+        Brick {
+            start: coordinate(start),
+            end: coordinate(end),
+        }
+    }
+
+    // Drop the brick until it lands, returns the brick in its final position together with all
+    // the unique bricks supporting it
     fn fall(
         &self,
         index: usize,
         grid: &mut HashMap<(isize, isize, isize), usize>,
-    ) -> HashSet<usize> {
+    ) -> (Brick, HashSet<usize>) {
         let mut down = 0;
         // Keep track of all the unique bricks supporting the current brick
         let mut supports = HashSet::new();
@@ -90,140 +220,187 @@ impl Brick {
             }
         }
 
-        // Return all the unique supports
-        supports
+        let settled = Brick {
+            start: (self.start.0, self.start.1, self.start.2 - down),
+            end: (self.end.0, self.end.1, self.end.2 - down),
+        };
+
+        (settled, supports)
     }
 }
 
-// -- Solution --
-pub struct Day;
-impl aoc::Solver for Day {
-    type Output1 = usize;
-    type Output2 = usize;
-
-    fn day() -> u8 {
-        22
-    }
+// All bricks after they have settled, together with the graph of which brick rests on which.
+// Bricks are ordered by height, so every brick comes after all of its supports.
+#[derive(Debug, Clone)]
+struct Stack {
+    bricks: Vec<Brick>,
+    // Bricks directly below every brick, empty if the brick rests on the ground
+    supports: Vec<Vec<usize>>,
+}
 
-    fn part1(input: &str) -> Self::Output1 {
-        let mut bricks: Vec<_> = input
-            .lines()
-            .map(|line| {
-                let (start, end) = line.split_once('~').unwrap();
-
-                let start: Vec<_> = start
-                    .splitn(3, ',')
-                    .map(|num| num.parse::<isize>().unwrap())
-                    .collect();
-                let start = (start[0], start[1], start[2]);
-
-                let end: Vec<_> = end
-                    .splitn(3, ',')
-                    .map(|num| num.parse::<isize>().unwrap())
-                    .collect();
-                let end = (end[0], end[1], end[2]);
-
-                Brick { start, end }
-            })
-            .collect();
+impl Stack {
+    fn settle(input: &str) -> Self {
+        let mut bricks: Vec<_> = input.lines().map(Brick::parse).collect();
 
-        // Sort the bricks from top to bottom
-        bricks.sort_by(|a, b| a.start.2.cmp(&b.start.2));
+        // Sort the bricks from bottom to top
+        bricks.sort_by_key(|brick| brick.start.2);
 
-        // Figure out which bricks are essential and can not be disintegrated
         let mut grid = HashMap::new();
-        let essential_bricks: HashSet<_> = bricks
+        let (bricks, supports): (Vec<_>, Vec<_>) = bricks
             .iter()
             .enumerate()
-            // Drop down each brick and get all the supporting bricks
-            .map(|(index, brick)| brick.fall(index, &mut grid))
-            // Only keep bricks that are supported by one other brick
-            // If that other brick is disintegrated this brick will fall
+            .map(|(index, brick)| {
+                let (brick, supports) = brick.fall(index, &mut grid);
+                let mut supports: Vec<_> = supports.into_iter().collect();
+                supports.sort();
+                (brick, supports)
+            })
+            .unzip();
+
+        Self { bricks, supports }
+    }
+
+    // Bricks that are the only support of another brick
+    fn essential(&self) -> HashSet<usize> {
+        self.supports
+            .iter()
             .filter(|supports| supports.len() == 1)
-            // Flatten out and collect to take out duplicate entries
             .flatten()
-            .collect();
-
-        bricks.len() - essential_bricks.len()
+            .copied()
+            .collect()
     }
 
-    fn part2(input: &str) -> Self::Output2 {
-        let mut bricks: Vec<_> = input
-            .lines()
-            .map(|line| {
-                let (start, end) = line.split_once('~').unwrap();
-
-                let start: Vec<_> = start
-                    .splitn(3, ',')
-                    .map(|num| num.parse::<isize>().unwrap())
-                    .collect();
-                let start = (start[0], start[1], start[2]);
-
-                let end: Vec<_> = end
-                    .splitn(3, ',')
-                    .map(|num| num.parse::<isize>().unwrap())
-                    .collect();
-                let end = (end[0], end[1], end[2]);
-
-                Brick { start, end }
-            })
-            .collect();
+    // Bricks directly on top of every brick
+    fn supporting(&self) -> Vec<Vec<usize>> {
+        let mut supporting = vec![Vec::new(); self.bricks.len()];
+        for (index, supports) in self.supports.iter().enumerate() {
+            for &support in supports {
+                supporting[support].push(index);
+            }
+        }
 
-        // Sort the bricks from top to bottom
-        bricks.sort_by(|a, b| a.start.2.cmp(&b.start.2));
+        supporting
+    }
 
-        // Figure out which bricks are essential and can not be disintegrated
-        let mut grid = HashMap::new();
-        let supports: Vec<_> = bricks
-            .iter()
-            .enumerate()
-            // Drop down each brick and get all the supporting bricks
-            .map(|(index, brick)| brick.fall(index, &mut grid))
-            .collect();
+    // All bricks that fall if the given brick is removed, in order from bottom to top.
+    // Simulates the chain reaction for a single brick, see chain_reactions for all of them at once.
+    fn falls_if_removed(&self, removed: usize) -> Vec<usize> {
+        let supporting = self.supporting();
+        let mut falling = vec![false; self.bricks.len()];
+        falling[removed] = true;
+
+        // Only bricks on top of falling bricks can fall, handling them from the bottom up makes
+        // sure all their supports have been decided
+        let mut candidates = BTreeSet::from_iter(supporting[removed].iter().copied());
+        let mut result = Vec::new();
+        while let Some(index) = candidates.pop_first() {
+            if self.supports[index].iter().all(|support| falling[*support]) {
+                falling[index] = true;
+                result.push(index);
+                candidates.extend(supporting[index].iter().copied());
+            }
+        }
 
-        let essential_bricks: HashSet<_> = supports
-            .iter()
-            .filter(|supports| supports.len() == 1)
-            // Flatten out and collect to take out duplicate entries
-            .flatten()
-            .collect();
-
-        let mut sum = 0;
-        for eliminate in essential_bricks {
-            let mut falling = HashSet::new();
-            falling.insert(*eliminate);
-            let mut previous = 0;
-
-            while previous != falling.len() {
-                previous = falling.len();
-
-                let new: HashSet<_> = supports
-                    .iter()
-                    .enumerate()
-                    .filter(|(_, supports)| {
-                        if supports.is_empty() {
-                            return false;
-                        }
+        result
+    }
 
-                        for support in *supports {
-                            if !falling.contains(support) {
-                                return false;
-                            }
-                        }
+    // Number of bricks that fall when each brick is removed.
+    // A brick falls when a brick is removed if every path from the ground to the brick goes
+    // through the removed brick, in other words if the removed brick dominates it. Since the
+    // supports come before the brick the immediate dominator is the lowest common ancestor of all
+    // supports in the dominator tree, which is found using binary lifting.
+    fn chain_reactions(&self) -> Vec<usize> {
+        let count = self.bricks.len();
+        // The ground is the root of the tree
+        let ground = count;
+        let levels = (usize::BITS - count.leading_zeros()) as usize + 1;
+
+        // ancestors[level][brick] is the dominator 2^level steps up the tree
+        let mut ancestors = vec![vec![ground; count + 1]; levels];
+        let mut depth = vec![0; count + 1];
+
+        let lca = |ancestors: &Vec<Vec<usize>>, depth: &Vec<usize>, mut a: usize, mut b: usize| {
+            if depth[a] < depth[b] {
+                std::mem::swap(&mut a, &mut b);
+            }
+            for level in (0..levels).rev() {
+                if depth[a] - depth[b] >= 1 << level {
+                    a = ancestors[level][a];
+                }
+            }
+            if a == b {
+                return a;
+            }
+            for level in (0..levels).rev() {
+                if ancestors[level][a] != ancestors[level][b] {
+                    a = ancestors[level][a];
+                    b = ancestors[level][b];
+                }
+            }
 
-                        true
-                    })
-                    .map(|(index, _)| index)
-                    .collect();
+            ancestors[0][a]
+        };
 
-                falling.extend(new);
+        for (index, supports) in self.supports.iter().enumerate() {
+            let dominator = supports
+                .iter()
+                .copied()
+                .reduce(|a, b| lca(&ancestors, &depth, a, b))
+                .unwrap_or(ground);
+
+            depth[index] = depth[dominator] + 1;
+            ancestors[0][index] = dominator;
+            for level in 1..levels {
+                ancestors[level][index] = ancestors[level - 1][ancestors[level - 1][index]];
             }
+        }
+
+        // Subtree sizes, children always come after their dominator
+        let mut size = vec![1; count + 1];
+        for index in (0..count).rev() {
+            size[ancestors[0][index]] += size[index];
+        }
+
+        size.into_iter().take(count).map(|size| size - 1).collect()
+    }
 
-            if !falling.is_empty() {
-                sum += falling.len() - 1;
+    // Every cube of every brick as 'X Y Z RRGGBB', this can be imported in voxel editors like Goxel
+    fn voxels(&self) -> String {
+        let mut output = String::from("# X Y Z RRGGBB\n");
+        for (index, brick) in self.bricks.iter().enumerate() {
+            // Spread out the colours so neighbouring bricks are easy to tell apart
+            let color = (index as u32).wrapping_mul(0x9E3779B9) >> 8;
+            for x in brick.start.0..=brick.end.0 {
+                for y in brick.start.1..=brick.end.1 {
+                    for z in brick.start.2..=brick.end.2 {
+                        writeln!(output, "{x} {y} {z} {color:06x}").unwrap();
+                    }
+                }
             }
         }
 
-        sum
+        output
+    }
+}
+
+// -- Solution --
+pub struct Day;
+impl aoc::Solver for Day {
+    type Output1 = usize;
+    type Output2 = usize;
+
+    fn day() -> u8 {
+        22
+    }
+
+    fn part1(input: &str) -> Self::Output1 {
+        let stack = Stack::settle(input);
+
+        // Bricks that are the only support of another brick can not be disintegrated
+        stack.bricks.len() - stack.essential().len()
+    }
+
+    fn part2(input: &str) -> Self::Output2 {
+        Stack::settle(input).chain_reactions().iter().sum()
     }
 }
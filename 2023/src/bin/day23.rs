@@ -1,12 +1,32 @@
 #![feature(test)]
-use std::collections::HashSet;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+};
 
 use anyhow::Result;
-use aoc::Solver;
+use aoc::{
+    graph::{Graph, Path},
+    Output, Solver,
+};
 
 // -- Runners --
 fn main() -> Result<()> {
-    Day::solve()
+    Day::solve_with(&[
+        // Draw the longest hike on the map instead of printing the answers
+        Output {
+            name: "render",
+            run: |input, _| {
+                let mut output = String::new();
+                for slippery in [true, false] {
+                    let maze = Maze::new(parse(input), slippery);
+                    let path = maze.longest_path();
+                    writeln!(output, "{}Length: {}\n", maze.render(&path), path.length)?;
+                }
+                Ok(output)
+            },
+        },
+    ])
 }
 
 #[cfg(test)]
@@ -33,6 +53,57 @@ mod tests {
         Day::test(Day::part2, "input", 6710)
     }
 
+    #[test]
+    fn junctions_test1() -> Result<()> {
+        Day::test(
+            |input| {
+                let maze = Maze::new(parse(input), false);
+                (maze.junctions.len(), maze.corridors.len())
+            },
+            "test-1",
+            (9, 24),
+        )
+    }
+
+    #[test]
+    fn sequential_test1() -> Result<()> {
+        Day::test(
+            |input| {
+                let maze = Maze::new(parse(input), false);
+                maze.graph.longest_path(0, 1, false).unwrap() == maze.longest_path()
+            },
+            "test-1",
+            true,
+        )
+    }
+
+    #[test]
+    fn render_test1() -> Result<()> {
+        Day::test(
+            |input| {
+                let maze = Maze::new(parse(input), true);
+                let path = maze.longest_path();
+                // Every step and the start are marked
+                maze.render(&path).matches('O').count()
+            },
+            "test-1",
+            95,
+        )
+    }
+
+    #[test]
+    fn parallel_corridors() {
+        // Both corridors between the two junctions in the middle, only the longer one is kept
+        let map = "#.#####\n#.....#\n#.###.#\n#.#...#\n#...#.#\n#####.#\n";
+        let maze = Maze::new(parse(map), false);
+        assert_eq!(maze.corridors[&(2, 3)].len(), 7);
+        assert_eq!(maze.graph.edges(2), &[(0, 1), (3, 8)]);
+
+        let path = maze.longest_path();
+        assert_eq!(path.length, 11);
+        assert_eq!(maze.render(&path).matches('O').count(), 12);
+    }
+
     // Benchmarks
     extern crate test;
     #[bench]
@@ -57,58 +128,182 @@ enum Tile {
     Right,
 }
 
-fn find_path(
-    position: (isize, isize),
-    end: (isize, isize),
-    map: &Vec<Vec<Tile>>,
-    visited: &mut Vec<Vec<bool>>,
-    slippery: bool,
-) -> (usize, (isize, isize)) {
-    visited[position.1 as usize][position.0 as usize] = true;
-    let size = (map[0].len() as isize, map.len() as isize);
-
-    let mut final_position = position;
-
-    let directions = [(0, -1), (0, 1), (-1, 0), (1, 0)];
-    let mut steps = 0;
-    for direction in directions {
-        let next_position = (position.0 + direction.0, position.1 + direction.1);
-
-        if next_position.0 < 0
-            || next_position.1 < 0
-            || next_position.0 >= size.0
-            || next_position.1 >= size.1
-            || visited[next_position.1 as usize][next_position.0 as usize]
-        {
-            continue;
+fn parse(input: &str) -> Vec<Vec<Tile>> {
+    input
+        .lines()
+        .map(|line| {
+            line.chars()
+                .map(|c| match c {
+                    '.' => Tile::Path,
+                    '#' => Tile::Forest,
+                    '^' => Tile::Up,
+                    'v' => Tile::Down,
+                    '<' => Tile::Left,
+                    '>' => Tile::Right,
+                    _ => unreachable!("Invalid input"),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+type Position = (usize, usize);
+
+// The maze compressed into a graph of junctions, the corridors between them become weighted edges
+struct Maze {
+    map: Vec<Vec<Tile>>,
+    junctions: Vec<Position>,
+    // Cells of the corridor between two junctions, excluding the junctions themselves. When there
+    // are multiple corridors between the same junctions only the longest one is kept.
+    corridors: HashMap<(usize, usize), Vec<Position>>,
+    graph: Graph,
+}
+
+impl Maze {
+    fn new(map: Vec<Vec<Tile>>, slippery: bool) -> Self {
+        let size = (map[0].len(), map.len());
+        let start = (1, 0);
+        let end = (size.0 - 2, size.1 - 1);
+
+        let neighbours = |position: Position| {
+            let map = &map;
+            [(0, -1), (0, 1), (-1, 0), (1, 0)].into_iter().filter_map(
+                move |direction: (isize, isize)| {
+                    let next = (
+                        position.0.checked_add_signed(direction.0)?,
+                        position.1.checked_add_signed(direction.1)?,
+                    );
+                    let tile = *map.get(next.1)?.get(next.0)?;
+                    (tile != Tile::Forest).then_some((next, direction, tile))
+                },
+            )
+        };
+
+        // Start, end and every cell where the path splits
+        let mut junctions = vec![start, end];
+        for (y, row) in map.iter().enumerate() {
+            for (x, tile) in row.iter().enumerate() {
+                if *tile != Tile::Forest && neighbours((x, y)).count() > 2 {
+                    junctions.push((x, y));
+                }
+            }
         }
+        let ids: HashMap<_, _> = junctions
+            .iter()
+            .enumerate()
+            .map(|(id, position)| (*position, id))
+            .collect();
 
-        let tile = map[next_position.1 as usize][next_position.0 as usize];
-        if tile == Tile::Forest {
-            continue;
+        // Follow every corridor from every junction until the next junction
+        let mut corridors: HashMap<_, Vec<_>> = HashMap::new();
+        for (from, &junction) in junctions.iter().enumerate() {
+            'corridor: for (mut position, _, _) in neighbours(junction) {
+                let mut previous = junction;
+                let mut cells = Vec::new();
+                while !ids.contains_key(&position) {
+                    cells.push(position);
+                    let Some((next, direction, tile)) =
+                        neighbours(position).find(|(next, _, _)| *next != previous)
+                    else {
+                        // Dead end
+                        continue 'corridor;
+                    };
+
+                    if slippery && !Self::downhill(tile, direction) {
+                        continue 'corridor;
+                    }
+
+                    previous = position;
+                    position = next;
+                }
+
+                // The slope right next to the junction also counts
+                let first = cells.first().copied().unwrap_or(position);
+                let direction = (
+                    first.0 as isize - junction.0 as isize,
+                    first.1 as isize - junction.1 as isize,
+                );
+                if slippery && !Self::downhill(map[first.1][first.0], direction) {
+                    continue;
+                }
+
+                let to = ids[&position];
+                let corridor = corridors.entry((from, to)).or_default();
+                if cells.len() >= corridor.len() {
+                    *corridor = cells;
+                }
+            }
         }
 
-        if slippery
-            && (tile == Tile::Up && direction != (0, -1)
-                || tile == Tile::Down && direction != (0, 1)
-                || tile == Tile::Left && direction != (-1, 0)
-                || tile == Tile::Right && direction != (1, 0))
-        {
-            continue;
+        // Sorted so the search always explores the edges in the same order
+        let mut edges = corridors
+            .iter()
+            .map(|(&(from, to), cells)| (from, to, cells.len() + 1))
+            .collect::<Vec<_>>();
+        edges.sort_unstable();
+
+        let mut graph = Graph::new(junctions.len());
+        for (from, to, weight) in edges {
+            graph.add_edge(from, to, weight);
         }
 
-        let (mut a, b) = find_path(next_position, end, map, visited, slippery);
-        a += 1;
+        Self {
+            map,
+            junctions,
+            corridors,
+            graph,
+        }
+    }
 
-        if a > steps && b == end {
-            steps = a;
-            final_position = b;
+    // Slopes can only be walked in the direction they point
+    fn downhill(tile: Tile, direction: (isize, isize)) -> bool {
+        match tile {
+            Tile::Up => direction == (0, -1),
+            Tile::Down => direction == (0, 1),
+            Tile::Left => direction == (-1, 0),
+            Tile::Right => direction == (1, 0),
+            _ => true,
         }
     }
 
-    visited[position.1 as usize][position.0 as usize] = false;
+    fn longest_path(&self) -> Path {
+        // The first two junctions are the start and the end
+        self.graph
+            .longest_path(0, 1, true)
+            .expect("The end should be reachable")
+    }
 
-    (steps, final_position)
+    // Draw the map with the path marked using 'O'
+    fn render(&self, path: &Path) -> String {
+        let mut cells = HashSet::new();
+        for pair in path.nodes.windows(2) {
+            cells.insert(self.junctions[pair[0]]);
+            cells.extend(self.corridors[&(pair[0], pair[1])].iter().copied());
+        }
+        cells.extend(path.nodes.last().map(|node| self.junctions[*node]));
+
+        self.map
+            .iter()
+            .enumerate()
+            .map(|(y, row)| {
+                let mut line: String = row
+                    .iter()
+                    .enumerate()
+                    .map(|(x, tile)| match tile {
+                        _ if cells.contains(&(x, y)) => 'O',
+                        Tile::Path => '.',
+                        Tile::Forest => '#',
+                        Tile::Up => '^',
+                        Tile::Down => 'v',
+                        Tile::Left => '<',
+                        Tile::Right => '>',
+                    })
+                    .collect();
+                line.push('\n');
+                line
+            })
+            .collect()
+    }
 }
 
 // -- Solution --
@@ -122,48 +317,10 @@ impl aoc::Solver for Day {
     }
 
     fn part1(input: &str) -> Self::Output1 {
-        let map: Vec<Vec<_>> = input
-            .lines()
-            .map(|line| {
-                line.chars()
-                    .map(|c| match c {
-                        '.' => Tile::Path,
-                        '#' => Tile::Forest,
-                        '^' => Tile::Up,
-                        'v' => Tile::Down,
-                        '<' => Tile::Left,
-                        '>' => Tile::Right,
-                        _ => unreachable!("Invalid input"),
-                    })
-                    .collect()
-            })
-            .collect();
-
-        let size = (map[0].len() as isize, map.len() as isize);
-        let mut visited = vec![vec![false; size.0 as usize]; size.1 as usize];
-        find_path((1, 0), (size.0 - 2, size.1 - 1), &map, &mut visited, true).0
+        Maze::new(parse(input), true).longest_path().length
     }
 
     fn part2(input: &str) -> Self::Output2 {
-        let map: Vec<Vec<_>> = input
-            .lines()
-            .map(|line| {
-                line.chars()
-                    .map(|c| match c {
-                        '.' => Tile::Path,
-                        '#' => Tile::Forest,
-                        '^' => Tile::Up,
-                        'v' => Tile::Down,
-                        '<' => Tile::Left,
-                        '>' => Tile::Right,
-                        _ => unreachable!("Invalid input"),
-                    })
-                    .collect()
-            })
-            .collect();
-
-        let size = (map[0].len() as isize, map.len() as isize);
-        let mut visited = vec![vec![false; size.0 as usize]; size.1 as usize];
-        find_path((1, 0), (size.0 - 2, size.1 - 1), &map, &mut visited, false).0
+        Maze::new(parse(input), false).longest_path().length
     }
 }
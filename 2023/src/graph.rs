@@ -0,0 +1,294 @@
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+/// Directed graph with weighted edges, nodes are numbered from 0.
+/// At most 64 nodes are supported so the visited nodes fit in a bitmask.
+#[derive(Debug, Clone, Default)]
+pub struct Graph {
+    edges: Vec<Vec<(usize, usize)>>,
+}
+
+/// A simple path and the total weight of its edges
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Path {
+    pub length: usize,
+    pub nodes: Vec<usize>,
+}
+
+impl Graph {
+    pub fn new(nodes: usize) -> Self {
+        assert!(nodes <= 64, "Graph can have at most 64 nodes");
+        Self {
+            edges: vec![Vec::new(); nodes],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty()
+    }
+
+    pub fn add_edge(&mut self, from: usize, to: usize, weight: usize) {
+        self.edges[from].push((to, weight));
+    }
+
+    /// Outgoing edges of a node as (destination, weight)
+    pub fn edges(&self, node: usize) -> &[(usize, usize)] {
+        &self.edges[node]
+    }
+
+    /// Find the longest path from start to end that does not visit a node twice.
+    /// Returns [None] if the end can not be reached.
+    pub fn longest_path(&self, start: usize, end: usize, parallel: bool) -> Option<Path> {
+        let search = LongestPath::new(self, end);
+        let best = AtomicUsize::new(0);
+
+        let path = if parallel {
+            search.parallel(start, &best)
+        } else {
+            let mut worker = Worker::new(&search, &best);
+            worker.visit(start, 1 << start, 0);
+            worker.best
+        };
+
+        path.map(|(length, nodes)| Path { length, nodes })
+    }
+}
+
+struct LongestPath<'a> {
+    graph: &'a Graph,
+    end: usize,
+    // If the end can only be reached from a single node, reaching that node means we have to go to
+    // the end, otherwise the end can not be reached anymore
+    last: Option<usize>,
+    // Heaviest edge into every node, used to bound what the unvisited nodes can still add
+    heaviest: Vec<usize>,
+    // Bitmask with a bit set for every node in the graph
+    all: u64,
+}
+
+impl<'a> LongestPath<'a> {
+    fn new(graph: &'a Graph, end: usize) -> Self {
+        let mut heaviest = vec![0; graph.len()];
+        let mut into_end = Vec::new();
+        for (from, edges) in graph.edges.iter().enumerate() {
+            for &(to, weight) in edges {
+                heaviest[to] = heaviest[to].max(weight);
+                if to == end && !into_end.contains(&from) {
+                    into_end.push(from);
+                }
+            }
+        }
+
+        let last = match into_end.as_slice() {
+            [last] => Some(*last),
+            _ => None,
+        };
+
+        // Shifting by the full width is not allowed, which happens for an empty graph
+        let all = u64::MAX.checked_shr(64 - graph.len() as u32).unwrap_or(0);
+
+        Self {
+            graph,
+            end,
+            last,
+            heaviest,
+            all,
+        }
+    }
+
+    // The nodes that have not been visited yet can add at most their heaviest edge
+    fn bound(&self, visited: u64) -> usize {
+        let mut unvisited = !visited & self.all;
+        let mut bound = 0;
+        while unvisited != 0 {
+            bound += self.heaviest[unvisited.trailing_zeros() as usize];
+            unvisited &= unvisited - 1;
+        }
+
+        bound
+    }
+
+    // Every branch of the first few steps is searched on its own thread
+    fn parallel(&self, start: usize, best: &AtomicUsize) -> Option<(usize, Vec<usize>)> {
+        const DEPTH: usize = 3;
+
+        // Expand the search breadth first to get enough independent branches
+        let mut frontier = vec![(vec![start], 1_u64 << start, 0)];
+        for _ in 0..DEPTH {
+            let mut next = Vec::new();
+            for (path, visited, length) in frontier {
+                let node = *path.last().unwrap();
+                if node == self.end {
+                    next.push((path, visited, length));
+                    continue;
+                }
+
+                for &(to, weight) in self.graph.edges(node) {
+                    if visited & (1 << to) == 0 {
+                        let mut path = path.clone();
+                        path.push(to);
+                        next.push((path, visited | 1 << to, length + weight));
+                    }
+                }
+            }
+            frontier = next;
+        }
+
+        let results = thread::scope(|scope| {
+            let handles = frontier
+                .into_iter()
+                .map(|(path, visited, length)| {
+                    scope.spawn(move || {
+                        let mut worker = Worker::new(self, best);
+                        let node = *path.last().unwrap();
+                        worker.path = path[..path.len() - 1].to_vec();
+                        worker.visit(node, visited, length);
+                        worker.best
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        // Pick the first longest path to keep the result deterministic
+        results
+            .into_iter()
+            .flatten()
+            .fold(None, |best, result| match best {
+                Some((length, _)) if length >= result.0 => best,
+                _ => Some(result),
+            })
+    }
+}
+
+// Depth first search on a single thread, the best length is shared between threads for pruning
+struct Worker<'a> {
+    search: &'a LongestPath<'a>,
+    shared: &'a AtomicUsize,
+    path: Vec<usize>,
+    best: Option<(usize, Vec<usize>)>,
+}
+
+impl<'a> Worker<'a> {
+    fn new(search: &'a LongestPath<'a>, shared: &'a AtomicUsize) -> Self {
+        Self {
+            search,
+            shared,
+            path: Vec::new(),
+            best: None,
+        }
+    }
+
+    fn visit(&mut self, node: usize, visited: u64, length: usize) {
+        self.path.push(node);
+
+        if node == self.search.end {
+            if self.best.as_ref().is_none_or(|(best, _)| length > *best) {
+                self.best = Some((length, self.path.clone()));
+                self.shared.fetch_max(length, Ordering::Relaxed);
+            }
+        } else if length + self.search.bound(visited) > self.shared.load(Ordering::Relaxed) {
+            for &(to, weight) in self.search.graph.edges(node) {
+                // From the last node before the end, going anywhere else means the end is cut off
+                if Some(node) == self.search.last && to != self.search.end {
+                    continue;
+                }
+
+                if visited & (1 << to) == 0 {
+                    self.visit(to, visited | 1 << to, length + weight);
+                }
+            }
+        }
+
+        self.path.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn undirected(nodes: usize, edges: &[(usize, usize, usize)]) -> Graph {
+        let mut graph = Graph::new(nodes);
+        for &(a, b, weight) in edges {
+            graph.add_edge(a, b, weight);
+            graph.add_edge(b, a, weight);
+        }
+        graph
+    }
+
+    #[test]
+    fn longest_path() {
+        let graph = undirected(
+            7,
+            &[
+                (0, 1, 2),
+                (0, 2, 3),
+                (1, 2, 1),
+                (1, 3, 4),
+                (2, 4, 2),
+                (3, 4, 5),
+                (3, 5, 1),
+                (4, 5, 3),
+                (5, 6, 2),
+            ],
+        );
+        let expected = Path {
+            length: 18,
+            nodes: vec![0, 2, 1, 3, 4, 5, 6],
+        };
+        assert_eq!(graph.longest_path(0, 6, false), Some(expected.clone()));
+        assert_eq!(graph.longest_path(0, 6, true), Some(expected));
+    }
+
+    #[test]
+    fn unreachable() {
+        let mut graph = Graph::new(3);
+        graph.add_edge(0, 1, 1);
+        graph.add_edge(2, 1, 1);
+        assert_eq!(graph.longest_path(0, 2, false), None);
+        assert_eq!(graph.longest_path(0, 2, true), None);
+    }
+
+    #[test]
+    fn empty() {
+        let graph = Graph::new(0);
+        assert!(graph.is_empty());
+        assert_eq!(graph.len(), 0);
+
+        let search = LongestPath::new(&graph, 0);
+        assert_eq!(search.all, 0);
+        assert_eq!(search.bound(0), 0);
+    }
+
+    #[test]
+    fn full_size() {
+        // A chain through all 64 nodes uses every bit of the visited mask
+        let mut graph = Graph::new(64);
+        for node in 0..63 {
+            graph.add_edge(node, node + 1, 1);
+        }
+        assert_eq!(LongestPath::new(&graph, 63).all, u64::MAX);
+
+        let path = graph.longest_path(0, 63, false).unwrap();
+        assert_eq!(path.length, 63);
+        assert_eq!(path.nodes, (0..64).collect::<Vec<_>>());
+        assert_eq!(graph.longest_path(0, 63, true), Some(path));
+    }
+
+    #[test]
+    #[should_panic(expected = "at most 64 nodes")]
+    fn too_many_nodes() {
+        Graph::new(65);
+    }
+}
@@ -1,10 +1,80 @@
 #![feature(test)]
 extern crate test;
 
+pub mod graph;
+pub mod nonogram;
+pub mod polygon;
+pub mod visualize;
+
 use core::fmt;
-use std::{fmt::Debug, fs};
+use std::{env, fmt::Debug, fs, sync::OnceLock};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+/// Something a day can print instead of the answers, selected using `--<name>` or
+/// `--<name>=<value>` on the command line
+pub struct Output {
+    pub name: &'static str,
+    /// Called with the puzzle input and the value of the flag
+    pub run: fn(&str, Option<&str>) -> Result<String>,
+}
+
+/// Command line options, parsed once by [Solver::solve_with]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Options {
+    /// Animate the solution live in the terminal using `--visualize[=ansi]`
+    pub visualize: bool,
+    /// Frame rate for the animation using `--fps=<n>`
+    pub fps: Option<u32>,
+    /// Requested outputs as the index into the available outputs and the value of the flag
+    pub outputs: Vec<(usize, Option<String>)>,
+}
+
+static OPTIONS: OnceLock<Options> = OnceLock::new();
+
+impl Options {
+    pub fn parse(args: impl IntoIterator<Item = String>, outputs: &[Output]) -> Result<Self> {
+        let mut options = Self::default();
+        for arg in args {
+            let (flag, value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag, Some(value)),
+                None => (arg.as_str(), None),
+            };
 
-use anyhow::{Context, Result};
+            match (flag, value) {
+                ("--visualize", None | Some("ansi")) => options.visualize = true,
+                ("--visualize", Some(format)) => {
+                    bail!("Unknown visualization format '{format}', only ansi is supported")
+                }
+                ("--fps", Some(fps)) => {
+                    let fps = fps
+                        .parse()
+                        .with_context(|| format!("Invalid frame rate '{fps}'"))?;
+                    options.fps = Some(fps);
+                }
+                _ => {
+                    let index = flag
+                        .strip_prefix("--")
+                        .and_then(|name| outputs.iter().position(|output| output.name == name))
+                        .ok_or_else(|| anyhow!("Unknown option '{arg}'"))?;
+                    options.outputs.push((index, value.map(str::to_owned)));
+                }
+            }
+        }
+
+        // Outputs replace the answers, so there would be nothing to animate
+        if (options.visualize || options.fps.is_some()) && !options.outputs.is_empty() {
+            bail!("--visualize and --fps can not be combined with other outputs");
+        }
+
+        Ok(options)
+    }
+
+    /// Options that were given to the runner, tests and benchmarks use the defaults
+    pub fn get() -> &'static Self {
+        OPTIONS.get_or_init(Self::default)
+    }
+}
 
 pub trait Solver {
     type Output1: fmt::Display + Debug + PartialEq;
@@ -28,8 +98,27 @@ pub trait Solver {
     }
 
     fn solve() -> Result<()> {
+        Self::solve_with(&[])
+    }
+
+    /// Solve both parts, or print the outputs that were requested on the command line instead
+    fn solve_with(outputs: &[Output]) -> Result<()> {
+        let options = Options::parse(env::args().skip(1), outputs)?;
         let input = fs::read_to_string(format!("input/{:02}/input", Self::day()))
             .with_context(|| format!("Failed to read 'input' for day {:02}", Self::day()))?;
+
+        for (index, value) in &options.outputs {
+            print!("{}", (outputs[*index].run)(&input, value.as_deref())?);
+        }
+
+        let requested = !options.outputs.is_empty();
+        OPTIONS
+            .set(options)
+            .map_err(|_| anyhow!("Options can only be set once"))?;
+        if requested {
+            return Ok(());
+        }
+
         println!("Part 1:\n{}", Self::part1(&input));
         println!("Part 2:\n{}", Self::part2(&input));
 
@@ -44,3 +133,43 @@ pub trait Solver {
         b.iter(|| f(&input));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUTS: &[Output] = &[Output {
+        name: "dot",
+        run: |_, value| Ok(value.unwrap_or_default().to_owned()),
+    }];
+
+    fn parse(args: &[&str]) -> Result<Options> {
+        Options::parse(args.iter().map(|arg| arg.to_string()), OUTPUTS)
+    }
+
+    #[test]
+    fn options() -> Result<()> {
+        assert_eq!(parse(&[])?, Options::default());
+        assert_eq!(
+            parse(&["--dot", "--dot=small"])?.outputs,
+            vec![(0, None), (0, Some("small".to_owned()))]
+        );
+        assert_eq!(
+            parse(&["--visualize", "--fps=30"])?,
+            Options {
+                visualize: true,
+                fps: Some(30),
+                outputs: Vec::new()
+            }
+        );
+        assert!(parse(&["--visualize=ansi"])?.visualize);
+
+        assert!(parse(&["--visualize=gif"]).is_err());
+        assert!(parse(&["--fps=fast"]).is_err());
+        assert!(parse(&["--render"]).is_err());
+        assert!(parse(&["--visualize", "--dot"]).is_err());
+        assert!(parse(&["--dot", "--fps=30"]).is_err());
+
+        Ok(())
+    }
+}
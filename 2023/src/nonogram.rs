@@ -0,0 +1,400 @@
+use core::fmt;
+
+use anyhow::{bail, Result};
+
+/// State of a single cell, unknown cells can be either operational or damaged
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+pub enum Spring {
+    Operational,
+    Damaged,
+    Unknown,
+}
+
+impl Spring {
+    pub fn convert(c: char) -> Self {
+        match c {
+            '.' => Self::Operational,
+            '#' => Self::Damaged,
+            '?' => Self::Unknown,
+            _ => unreachable!("Invalid input"),
+        }
+    }
+}
+
+impl fmt::Display for Spring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Spring::Operational => write!(f, "."),
+            Spring::Damaged => write!(f, "#"),
+            Spring::Unknown => write!(f, "?"),
+        }
+    }
+}
+
+/// Dynamic programming tables for a single line.
+/// A boundary is a position where the next run of damaged springs is allowed to start.
+struct Table<'a> {
+    line: &'a [Spring],
+    clues: &'a [usize],
+    // Number of operational cells before every position
+    operational: Vec<usize>,
+    // prefix[i][j]: ways to fill line[..i] with the first j runs, ending on a boundary at i
+    prefix: Vec<Vec<usize>>,
+    // suffix[i][j]: ways to fill line[i..] with the runs from j, starting on a boundary at i
+    suffix: Vec<Vec<usize>>,
+    // Set when a count in the suffix table did not fit and was saturated
+    overflow: bool,
+}
+
+impl<'a> Table<'a> {
+    fn new(line: &'a [Spring], clues: &'a [usize]) -> Self {
+        let operational = std::iter::once(0)
+            .chain(line.iter().scan(0, |count, spring| {
+                *count += (*spring == Spring::Operational) as usize;
+                Some(*count)
+            }))
+            .collect();
+
+        let empty = vec![vec![0; clues.len() + 1]; line.len() + 1];
+        let mut table = Self {
+            line,
+            clues,
+            operational,
+            prefix: empty.clone(),
+            suffix: empty,
+            overflow: false,
+        };
+
+        // Counts can get very large, saturating keeps the tables usable for deciding what is possible
+        let len = line.len();
+        table.prefix[0][0] = 1;
+        for (i, spring) in line.iter().enumerate() {
+            for j in 0..=clues.len() {
+                let ways = table.prefix[i][j];
+                if ways == 0 {
+                    continue;
+                }
+
+                if *spring != Spring::Damaged {
+                    table.prefix[i + 1][j] = table.prefix[i + 1][j].saturating_add(ways);
+                }
+                if let Some(next) = table.place(i, j) {
+                    table.prefix[next][j + 1] = table.prefix[next][j + 1].saturating_add(ways);
+                }
+            }
+        }
+
+        table.suffix[len][clues.len()] = 1;
+        for i in (0..len).rev() {
+            for j in 0..=clues.len() {
+                let mut ways = 0;
+                if line[i] != Spring::Damaged {
+                    ways = table.suffix[i + 1][j];
+                }
+                if let Some(next) = table.place(i, j) {
+                    ways = table.suffix[next][j + 1]
+                        .checked_add(ways)
+                        .unwrap_or_else(|| {
+                            table.overflow = true;
+                            usize::MAX
+                        });
+                }
+                table.suffix[i][j] = ways;
+            }
+        }
+
+        table
+    }
+
+    // Check if run j can start at position i, returns the boundary after the run (including the
+    // operational spring that separates it from the next run)
+    fn place(&self, i: usize, j: usize) -> Option<usize> {
+        let length = *self.clues.get(j)?;
+        let end = i + length;
+        if end > self.line.len() || self.operational[end] != self.operational[i] {
+            return None;
+        }
+
+        match self.line.get(end) {
+            None => Some(end),
+            Some(Spring::Damaged) => None,
+            Some(_) => Some(end + 1),
+        }
+    }
+
+    fn possible(&self) -> bool {
+        self.suffix[0][0] > 0
+    }
+}
+
+/// Count the number of ways the unknown springs can be filled in so that the runs of damaged
+/// springs match the clues. Fails if the count does not fit in a [usize].
+pub fn count(line: &[Spring], clues: &[usize]) -> Result<usize> {
+    let table = Table::new(line, clues);
+    if table.overflow {
+        bail!("Number of arrangements does not fit in a usize");
+    }
+
+    Ok(table.suffix[0][0])
+}
+
+/// All valid ways to fill in the unknown springs
+pub fn arrangements(line: &[Spring], clues: &[usize]) -> Vec<Vec<Spring>> {
+    // Only follow branches that the table says lead to at least one arrangement
+    fn fill(
+        table: &Table,
+        i: usize,
+        j: usize,
+        current: &mut Vec<Spring>,
+        result: &mut Vec<Vec<Spring>>,
+    ) {
+        if i == table.line.len() {
+            result.push(current.clone());
+            return;
+        }
+
+        if table.line[i] != Spring::Damaged && table.suffix[i + 1][j] > 0 {
+            current.push(Spring::Operational);
+            fill(table, i + 1, j, current, result);
+            current.pop();
+        }
+
+        if let Some(next) = table
+            .place(i, j)
+            .filter(|&next| table.suffix[next][j + 1] > 0)
+        {
+            let length = current.len();
+            current.extend(std::iter::repeat_n(Spring::Damaged, table.clues[j]));
+            if next > i + table.clues[j] {
+                current.push(Spring::Operational);
+            }
+            fill(table, next, j + 1, current, result);
+            current.truncate(length);
+        }
+    }
+
+    let table = Table::new(line, clues);
+    let mut result = Vec::new();
+    if table.possible() {
+        fill(
+            &table,
+            0,
+            0,
+            &mut Vec::with_capacity(line.len()),
+            &mut result,
+        );
+    }
+
+    result
+}
+
+/// Fill in every cell that has the same state in all valid arrangements, cells that can be both
+/// stay unknown. Returns [None] if there is no valid arrangement.
+pub fn solve_line(line: &[Spring], clues: &[usize]) -> Option<Vec<Spring>> {
+    let table = Table::new(line, clues);
+    if !table.possible() {
+        return None;
+    }
+
+    let mut operational = vec![false; line.len()];
+    // Difference array of the cells covered by a run, avoids marking every cell of every run
+    let mut damaged = vec![0_isize; line.len() + 1];
+
+    for i in 0..line.len() {
+        for j in 0..=clues.len() {
+            if table.prefix[i][j] == 0 {
+                continue;
+            }
+
+            if line[i] != Spring::Damaged && table.suffix[i + 1][j] > 0 {
+                operational[i] = true;
+            }
+
+            if let Some(next) = table
+                .place(i, j)
+                .filter(|&next| table.suffix[next][j + 1] > 0)
+            {
+                let end = i + table.clues[j];
+                damaged[i] += 1;
+                damaged[end] -= 1;
+                if next > end {
+                    operational[end] = true;
+                }
+            }
+        }
+    }
+
+    let solved = operational
+        .iter()
+        .zip(&damaged)
+        .scan(0, |covered, (&operational, delta)| {
+            *covered += delta;
+            Some(match (operational, *covered > 0) {
+                (true, true) => Spring::Unknown,
+                (true, false) => Spring::Operational,
+                (false, _) => Spring::Damaged,
+            })
+        })
+        .collect();
+
+    Some(solved)
+}
+
+/// Puzzle where both the rows and the columns have clues
+#[derive(Debug, Clone)]
+pub struct Nonogram {
+    pub rows: Vec<Vec<usize>>,
+    pub columns: Vec<Vec<usize>>,
+}
+
+pub type Grid = Vec<Vec<Spring>>;
+
+impl Nonogram {
+    pub fn new(rows: Vec<Vec<usize>>, columns: Vec<Vec<usize>>) -> Self {
+        Self { rows, columns }
+    }
+
+    /// Apply the line solver to all rows and columns until nothing changes anymore.
+    /// Returns [None] if the grid contradicts the clues.
+    pub fn propagate(&self, mut grid: Grid) -> Option<Grid> {
+        loop {
+            let mut changed = false;
+
+            for (y, clues) in self.rows.iter().enumerate() {
+                let solved = solve_line(&grid[y], clues)?;
+                changed |= solved != grid[y];
+                grid[y] = solved;
+            }
+
+            for (x, clues) in self.columns.iter().enumerate() {
+                let column = grid.iter().map(|row| row[x]).collect::<Vec<_>>();
+                let solved = solve_line(&column, clues)?;
+                changed |= solved != column;
+                for (row, spring) in grid.iter_mut().zip(solved) {
+                    row[x] = spring;
+                }
+            }
+
+            if !changed {
+                return Some(grid);
+            }
+        }
+    }
+
+    /// Solve the puzzle using line solving, when that gets stuck an unknown cell is guessed.
+    /// Returns the first solution that is found.
+    pub fn solve(&self) -> Option<Grid> {
+        let grid = vec![vec![Spring::Unknown; self.columns.len()]; self.rows.len()];
+        self.search(grid)
+    }
+
+    fn search(&self, grid: Grid) -> Option<Grid> {
+        let grid = self.propagate(grid)?;
+
+        let Some((x, y)) = grid.iter().enumerate().find_map(|(y, row)| {
+            row.iter()
+                .position(|spring| *spring == Spring::Unknown)
+                .map(|x| (x, y))
+        }) else {
+            return Some(grid);
+        };
+
+        [Spring::Damaged, Spring::Operational]
+            .into_iter()
+            .find_map(|guess| {
+                let mut grid = grid.clone();
+                grid[y][x] = guess;
+                self.search(grid)
+            })
+    }
+}
+
+pub fn render(grid: &Grid) -> String {
+    grid.iter()
+        .map(|row| {
+            row.iter()
+                .map(|spring| spring.to_string())
+                .collect::<String>()
+                + "\n"
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Same row format as day 12, e.g. "???.### 1,1,3"
+    fn parse(line: &str) -> (Vec<Spring>, Vec<usize>) {
+        let (springs, list) = line.split_once(' ').unwrap();
+        (
+            springs.chars().map(Spring::convert).collect(),
+            list.split(',').map(|num| num.parse().unwrap()).collect(),
+        )
+    }
+
+    fn to_string(springs: &[Spring]) -> String {
+        springs.iter().map(|spring| spring.to_string()).collect()
+    }
+
+    #[test]
+    fn arrangements_test1() {
+        let (springs, list) = parse(".??..??...?##. 1,1,3");
+        let found = arrangements(&springs, &list)
+            .iter()
+            .map(|springs| to_string(springs))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                "..#...#...###.",
+                "..#..#....###.",
+                ".#....#...###.",
+                ".#...#....###.",
+            ]
+        );
+    }
+
+    #[test]
+    fn forced_cells() {
+        let rows = [
+            ("???.### 1,1,3", "#.#.###"),
+            (".??..??...?##. 1,1,3", ".??..??...###."),
+            ("?#?#?#?#?#?#?#? 1,3,1,6", ".#.###.#.######"),
+            ("????.#...#... 4,1,1", "####.#...#..."),
+            ("????.######..#####. 1,6,5", "????.######..#####."),
+            ("?###???????? 3,2,1", ".###.???????"),
+        ];
+        for (line, expected) in rows {
+            let (springs, list) = parse(line);
+            let solved = solve_line(&springs, &list).unwrap();
+            assert_eq!(to_string(&solved), expected);
+        }
+    }
+
+    #[test]
+    fn contradiction() {
+        let (springs, list) = parse("#.# 3");
+        assert_eq!(solve_line(&springs, &list), None);
+        assert_eq!(count(&springs, &list).unwrap(), 0);
+    }
+
+    #[test]
+    fn count_overflow() {
+        // 171 choose 30 arrangements, far more than fits in a usize
+        let springs = vec![Spring::Unknown; 200];
+        assert!(count(&springs, &[1; 30]).is_err());
+        assert_eq!(count(&springs[..10], &[1; 2]).unwrap(), 36);
+    }
+
+    #[test]
+    fn solve_2d() {
+        // A small arrow pointing up
+        let puzzle = Nonogram::new(
+            vec![vec![1], vec![3], vec![5], vec![1], vec![1]],
+            vec![vec![1], vec![2], vec![5], vec![2], vec![1]],
+        );
+        let grid = puzzle.solve().unwrap();
+        assert_eq!(render(&grid), "..#..\n.###.\n#####\n..#..\n..#..\n");
+    }
+}
@@ -0,0 +1,219 @@
+use anyhow::{bail, Result};
+
+pub type Point = (i128, i128);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    pub fn offset(&self) -> Point {
+        match self {
+            Direction::Up => (0, -1),
+            Direction::Down => (0, 1),
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+        }
+    }
+}
+
+/// Closed polygon on a grid, the last vertex connects back to the first one.
+/// Everything is computed using i128 so large coordinates do not overflow.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Polygon {
+    vertices: Vec<Point>,
+}
+
+impl Polygon {
+    /// Vertices in order, vertices in the middle of a straight edge are removed
+    pub fn from_vertices(vertices: impl IntoIterator<Item = Point>) -> Self {
+        let mut result: Vec<Point> = Vec::new();
+        for vertex in vertices {
+            if result.last() == Some(&vertex) {
+                continue;
+            }
+
+            // Drop the previous vertex if it is on the line between its neighbours
+            if let [.., a, b] = result.as_slice() {
+                if straight(*a, *b, vertex) {
+                    result.pop();
+                }
+            }
+            result.push(vertex);
+        }
+
+        // The same applies around the point where the polygon closes
+        while result.len() > 2 {
+            let n = result.len();
+            if result[n - 1] == result[0] || straight(result[n - 2], result[n - 1], result[0]) {
+                result.pop();
+            } else if straight(result[n - 1], result[0], result[1]) {
+                result.remove(0);
+            } else {
+                break;
+            }
+        }
+
+        Self { vertices: result }
+    }
+
+    /// Walk the steps starting from the origin, the steps have to end up back at the origin
+    pub fn from_steps(steps: impl IntoIterator<Item = (Direction, i128)>) -> Result<Self> {
+        let mut position = (0, 0);
+        let mut vertices = vec![position];
+        for (direction, length) in steps {
+            let (dx, dy) = direction.offset();
+            position = (position.0 + dx * length, position.1 + dy * length);
+            vertices.push(position);
+        }
+
+        if position != (0, 0) {
+            bail!("The steps end at {position:?} instead of returning to the start");
+        }
+
+        Ok(Self::from_vertices(vertices))
+    }
+
+    pub fn vertices(&self) -> &[Point] {
+        &self.vertices
+    }
+
+    fn edges(&self) -> impl Iterator<Item = (Point, Point)> + '_ {
+        self.vertices
+            .iter()
+            .copied()
+            .zip(self.vertices.iter().copied().cycle().skip(1))
+    }
+
+    /// Twice the enclosed area using the shoelace formula, this is always an integer
+    pub fn twice_area(&self) -> i128 {
+        self.edges()
+            .map(|(a, b)| a.0 * b.1 - b.0 * a.1)
+            .sum::<i128>()
+            .abs()
+    }
+
+    /// Area enclosed by the edges, rounded down for polygons that are not rectilinear
+    pub fn area(&self) -> i128 {
+        self.twice_area() / 2
+    }
+
+    /// Number of grid points on the edges
+    pub fn boundary_points(&self) -> i128 {
+        self.edges()
+            .map(|(a, b)| gcd((b.0 - a.0).abs(), (b.1 - a.1).abs()))
+            .sum()
+    }
+
+    /// Number of grid points strictly inside, using Pick's theorem: A = i + b/2 - 1
+    pub fn interior_points(&self) -> i128 {
+        (self.twice_area() - self.boundary_points()) / 2 + 1
+    }
+
+    /// Number of grid points inside or on the edges
+    pub fn lattice_points(&self) -> i128 {
+        self.interior_points() + self.boundary_points()
+    }
+
+    /// Check that the polygon only has horizontal and vertical edges and that no two edges
+    /// touch, except for edges that share a vertex
+    pub fn validate(&self) -> Result<()> {
+        if self.vertices.len() < 4 {
+            bail!(
+                "A polygon needs at least 4 corners, found {}",
+                self.vertices.len()
+            );
+        }
+
+        let edges: Vec<_> = self.edges().collect();
+        if let Some((a, b)) = edges.iter().find(|(a, b)| a.0 != b.0 && a.1 != b.1) {
+            bail!("Edge from {a:?} to {b:?} is not horizontal or vertical");
+        }
+
+        // Axis aligned edges intersect if their bounding boxes overlap
+        let n = edges.len();
+        for i in 0..n {
+            for j in i + 2..n {
+                // The last edge is connected to the first edge
+                if i == 0 && j == n - 1 {
+                    continue;
+                }
+
+                let ((a, b), (c, d)) = (edges[i], edges[j]);
+                let overlap = |a: i128, b: i128, c: i128, d: i128| {
+                    a.min(b) <= c.max(d) && c.min(d) <= a.max(b)
+                };
+                if overlap(a.0, b.0, c.0, d.0) && overlap(a.1, b.1, c.1, d.1) {
+                    bail!("Edge from {a:?} to {b:?} intersects edge from {c:?} to {d:?}");
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Check if b is on the straight line from a to c, turning back does not count
+fn straight(a: Point, b: Point, c: Point) -> bool {
+    let (first, second) = ((b.0 - a.0, b.1 - a.1), (c.0 - b.0, c.1 - b.1));
+    let cross = first.0 * second.1 - first.1 * second.0;
+    let dot = first.0 * second.0 + first.1 * second.1;
+
+    cross == 0 && dot > 0
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    if b == 0 {
+        return a;
+    }
+
+    gcd(b, a % b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_polygons() {
+        use Direction::*;
+
+        let open = Polygon::from_steps([(Right, 2), (Down, 2), (Left, 1)]);
+        assert!(open.is_err());
+
+        // A figure eight crosses itself
+        let crossing = Polygon::from_steps([
+            (Right, 2),
+            (Down, 2),
+            (Right, 2),
+            (Up, 1),
+            (Left, 4),
+            (Down, 1),
+            (Right, 1),
+            (Up, 2),
+            (Left, 1),
+        ])
+        .unwrap();
+        assert!(crossing.validate().is_err());
+
+        let square = Polygon::from_steps([(Right, 3), (Down, 3), (Left, 3), (Up, 3)]).unwrap();
+        assert!(square.validate().is_ok());
+        assert_eq!((square.area(), square.lattice_points()), (9, 16));
+    }
+
+    #[test]
+    fn large_coordinates() {
+        use Direction::*;
+
+        // Far beyond what fits in an i64 when multiplied
+        let size = 1 << 40;
+        let square =
+            Polygon::from_steps([(Right, size), (Down, size), (Left, size), (Up, size)]).unwrap();
+        assert_eq!(square.area(), size * size);
+        assert_eq!(square.lattice_points(), (size + 1) * (size + 1));
+    }
+}
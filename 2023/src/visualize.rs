@@ -0,0 +1,79 @@
+use std::{
+    io::{self, Write},
+    thread,
+    time::Duration,
+};
+
+use anyhow::Result;
+
+use crate::Options;
+
+pub type Color = [u8; 3];
+
+/// Anything that can be drawn as a grid of cells.
+/// Both years define this trait and [Color] the same way, so a day can be ported between them.
+pub trait Render {
+    /// Width and height in cells
+    fn size(&self) -> (usize, usize);
+
+    /// Index into the palette for the cell at the given position
+    fn cell(&self, x: usize, y: usize) -> u8;
+
+    /// Colours used by the cells, indexed by the value returned from [Render::cell]
+    fn palette(&self) -> &[Color];
+}
+
+/// Live animation in the terminal, every frame is drawn in place using ANSI colours
+pub struct Terminal<W: Write> {
+    out: W,
+    first: bool,
+    frame_time: Duration,
+}
+
+impl Terminal<io::Stdout> {
+    /// Create a terminal renderer if it was requested in the options given to the runner
+    pub fn from_options() -> Option<Self> {
+        let options = Options::get();
+        options
+            .visualize
+            .then(|| Self::new(io::stdout(), options.fps.unwrap_or(10)))
+    }
+}
+
+impl<W: Write> Terminal<W> {
+    pub fn new(out: W, fps: u32) -> Self {
+        Self {
+            out,
+            first: true,
+            frame_time: Duration::from_secs(1) / fps.max(1),
+        }
+    }
+
+    pub fn frame(&mut self, render: &impl Render) -> Result<()> {
+        let mut buffer = String::new();
+
+        // Clear the screen once, after that only move the cursor back to the top
+        if self.first {
+            buffer += "\x1b[2J";
+            self.first = false;
+        }
+        buffer += "\x1b[H";
+
+        let (width, height) = render.size();
+        let palette = render.palette();
+        for y in 0..height {
+            for x in 0..width {
+                let [r, g, b] = palette[render.cell(x, y) as usize];
+                // Two characters per cell to keep them roughly square
+                buffer += &format!("\x1b[48;2;{r};{g};{b}m  ");
+            }
+            buffer += "\x1b[0m\n";
+        }
+
+        self.out.write_all(buffer.as_bytes())?;
+        self.out.flush()?;
+        thread::sleep(self.frame_time);
+
+        Ok(())
+    }
+}